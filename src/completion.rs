@@ -0,0 +1,185 @@
+use std::io::Write;
+use structopt::clap::{App, Shell};
+
+use super::{get_vtree_path, load_tree, _TREES};
+
+/// Kind of a command line argument that is completed by calling back into vtree.
+#[derive(Debug, PartialEq)]
+enum Slot {
+    Tree,  // A tree name.
+    Path(String),  // A virtual path in the given tree.
+    TreePath,  // A tree name followed by a virtual path, such as "tree/a/b".
+}
+
+/// Find the kind of the argument at `index` of `words`, where `words[0]` is the
+/// program name. None is returned if the argument is not a tree or a virtual path.
+fn find_slot(words: &[String], index: usize) -> Option<Slot> {
+    if index < 2 || index > words.len() {
+        return None;
+    }
+    let positionals: Vec<&String> = words[2..index]
+        .iter()
+        .filter(|word| !word.starts_with('-'))
+        .collect();
+    let tree = || Slot::Path(positionals[0].to_string());
+    match (words[1].as_str(), positionals.len()) {
+        ("tree" | "enter" | "browse" | "remove", 0) => Some(Slot::Tree),
+        ("rename" | "copy" | "describe", 0) => Some(Slot::Tree),
+        ("add" | "mkdir" | "rm" | "mv" | "ls" | "cat" | "desc", 0) => Some(Slot::Tree),
+        ("mkdir" | "rm" | "mv" | "ls" | "cat" | "desc", 1) => Some(tree()),
+        ("add" | "mv", 2) => Some(tree()),
+        ("resolve" | "paths", 0) => Some(Slot::TreePath),
+        _ => None,
+    }
+}
+
+/// Names of the trees starting with `prefix`.
+fn tree_names(prefix: &str) -> std::io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(get_vtree_path(true)?.join(_TREES))? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                if name.starts_with(prefix) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Print the candidates for the argument at `index` of `words`, one per line.
+/// Returns false if the argument is not completed dynamically, so that the shell
+/// falls back to the static completion.
+pub fn complete(index: usize, words: &[String]) -> std::io::Result<bool> {
+    let slot = match find_slot(words, index) {
+        Some(slot) => slot,
+        None => return Ok(false),
+    };
+    let word = words.get(index).map(|word| word.as_str()).unwrap_or("");
+    let candidates = match slot {
+        Slot::Tree => tree_names(word)?,
+        Slot::Path(tree) => load_tree(&tree)?.complete_path(word),
+        Slot::TreePath => match word.split_once('/') {
+            Some((tree, vpath)) => load_tree(tree)?
+                .complete_path(vpath)
+                .into_iter()
+                .map(|path| format!("{}/{}", tree, path))
+                .collect(),
+            None => tree_names(word)?.into_iter().map(|name| name + "/").collect(),
+        },
+    };
+    let mut stdout = std::io::stdout().lock();
+    for candidate in candidates {
+        writeln!(stdout, "{}", candidate)?;
+    }
+    Ok(true)
+}
+
+/// Write the completion script of `shell`. The script generated by clap is
+/// extended to complete the trees and virtual paths with `vtree complete`.
+pub fn generate(mut app: App, shell: Shell, out: &mut dyn Write) -> std::io::Result<()> {
+    let mut script = Vec::new();
+    app.gen_completions_to("vtree", shell, &mut script);
+    // clap does not hide `vtree complete` from the completion
+    let script: String = String::from_utf8_lossy(&script)
+        .lines()
+        .filter(|line| {
+            line.trim() != "\"complete:\" \\"
+                && !line.ends_with("__fish_use_subcommand\" -f -a \"complete\"")
+        })
+        .map(|line| line.replace(" complete help", " help") + "\n")
+        .collect();
+    match shell {
+        Shell::Bash => {
+            let script = script.replace(
+                "complete -F _vtree -o bashdefault -o default vtree",
+                _BASH_DYNAMIC,
+            );
+            out.write_all(script.as_bytes())
+        }
+        Shell::Zsh => {
+            // the generated `_vtree` is called by the dynamic one
+            let script = script
+                .replace("\n_vtree() {", "\n_vtree_static() {")
+                .replace("\n_vtree \"$@\"", &format!("\n{}\n_vtree \"$@\"", _ZSH_DYNAMIC));
+            out.write_all(script.as_bytes())
+        }
+        Shell::Fish => {
+            out.write_all(script.as_bytes())?;
+            out.write_all(_FISH_DYNAMIC.as_bytes())
+        }
+        _ => out.write_all(script.as_bytes()),
+    }
+}
+
+const _BASH_DYNAMIC: &str = r#"_vtree_dynamic() {
+    local candidates
+    if candidates=$(vtree complete "${COMP_CWORD}" "${COMP_WORDS[@]}" 2>/dev/null); then
+        local IFS=$'\n'
+        COMPREPLY=($(compgen -W "${candidates}" -- "${COMP_WORDS[COMP_CWORD]}"))
+        if [[ ${#COMPREPLY[@]} -eq 1 && ${COMPREPLY[0]} == */ ]]; then
+            compopt -o nospace
+        fi
+        return 0
+    fi
+    _vtree "$@"
+}
+
+complete -F _vtree_dynamic -o bashdefault -o default vtree"#;
+
+const _ZSH_DYNAMIC: &str = r#"_vtree() {
+    local out
+    if out=$(vtree complete $((CURRENT - 1)) "${words[@]}" 2>/dev/null); then
+        local -a candidates
+        candidates=(${(f)out})
+        compadd -S '' -- ${(M)candidates:#*/}
+        compadd -- ${candidates:#*/}
+        return
+    fi
+    _vtree_static "$@"
+}
+"#;
+
+const _FISH_DYNAMIC: &str = r#"
+function __vtree_complete
+    set -l words (commandline -opc)
+    vtree complete (count $words) $words (commandline -ct) 2>/dev/null
+end
+complete -c vtree -n "__vtree_complete >/dev/null" -f -a "(__vtree_complete)"
+"#;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        line.split(' ').map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn test_find_slot() {
+        assert_eq!(find_slot(&words("vtree enter Pro"), 2), Some(Slot::Tree));
+        assert_eq!(find_slot(&words("vtree en"), 1), None);
+        assert_eq!(find_slot(&words("vtree ls -d data a/"), 4), Some(Slot::Path("data".to_string())));
+        assert_eq!(find_slot(&words("vtree add data file.txt "), 3), None);
+        assert_eq!(find_slot(&words("vtree add data file.txt a"), 4), Some(Slot::Path("data".to_string())));
+        assert_eq!(find_slot(&words("vtree resolve data/a"), 2), Some(Slot::TreePath));
+        assert_eq!(find_slot(&words("vtree enter data "), 3), None);
+        assert_eq!(find_slot(&words("vtree list "), 2), None);
+    }
+
+    #[test]
+    fn test_generate() {
+        let app = || App::new("vtree").subcommand(App::new("enter")).subcommand(App::new("complete"));
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let mut out = Vec::new();
+            generate(app(), shell, &mut out).unwrap();
+            let script = String::from_utf8(out).unwrap();
+            assert!(script.contains("vtree complete "));
+            assert!(!script.contains("complete help") && !script.contains("\"complete:\""));
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+
+use super::get_vtree_path;
+use super::vtui::keymap::{Keymap, KeymapConfig};
+use super::vtui::theme::Palette;
+
+const _CONFIG: &str = "config";
+
+/// Configuration of vtree.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    pub history_size: usize,  // Number of commands kept in the history.
+    pub scrollback_size: usize,  // Number of output lines kept in the terminal.
+    pub prompt: String,  // Prompt template. See vtui::prompt for the placeholders.
+    pub theme: String,  // Name of a built-in theme or a theme in `themes`.
+    pub themes: HashMap<String, HashMap<String, String>>,  // Custom themes.
+    pub colors: HashMap<String, String>,  // Styles overriding the ones of the theme.
+    pub shell: Option<Vec<String>>,  // Shell program and arguments used by `call --shell`.
+    pub opener: Option<String>,  // Program used by `open`.
+    pub keymap: KeymapConfig,
+    pub aliases: HashMap<String, String>,  // Aliases of the virtual commands.
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            history_size: 500,
+            scrollback_size: 1000,
+            prompt: "/[{tree}]/{path} > ".to_string(),
+            theme: "dark".to_string(),
+            themes: HashMap::new(),
+            colors: HashMap::new(),
+            shell: None,
+            opener: None,
+            keymap: KeymapConfig::default(),
+            aliases: HashMap::new(),
+        }
+    }
+}
+
+fn invalid_data(msg: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+/// Path to the project config file.
+pub fn project_config_path() -> std::io::Result<PathBuf> {
+    Ok(get_vtree_path(false)?.join(_CONFIG))
+}
+
+/// Path to the user config file, such as ~/.config/vtree/config.
+pub fn user_config_path() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    base.map(|base| base.join("vtree").join(_CONFIG))
+}
+
+/// Read a config file as a json value. An empty object is returned if the file
+/// does not exist.
+fn read_value(path: &PathBuf) -> std::io::Result<Value> {
+    if !path.exists() {
+        return Ok(Value::Object(Map::new()));
+    }
+    let text = std::fs::read_to_string(path)?;
+    serde_json::from_str(&text)
+        .map_err(|err| invalid_data(format!("Invalid config file {}: {}", path.display(), err)))
+}
+
+/// Recursively merge `other` into `base`. Values in `other` take precedence.
+fn merge(base: &mut Value, other: Value) {
+    match (base, other) {
+        (Value::Object(base), Value::Object(other)) => {
+            for (key, value) in other {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge(base_value, value),
+                    None => { base.insert(key, value); }
+                }
+            }
+        }
+        (base, other) => *base = other,
+    }
+}
+
+/// Get the value at a dotted key such as "colors.error".
+fn get_key<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    key.split('.').try_fold(value, |value, part| value.get(part))
+}
+
+/// Remove the value at a dotted key if it exists.
+fn remove_key(value: &mut Value, key: &str) {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let last = parts.pop().unwrap();
+    let parent = parts.into_iter().try_fold(value, |value, part| value.get_mut(part));
+    if let Some(Value::Object(obj)) = parent {
+        obj.remove(last);
+    }
+}
+
+/// Set the value at a dotted key, creating objects on the way.
+fn set_key(value: &mut Value, key: &str, new: Value) -> std::io::Result<()> {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let last = parts.pop().unwrap();
+    let mut current = value;
+    for part in parts {
+        let obj = current
+            .as_object_mut()
+            .ok_or_else(|| invalid_data(format!("{} is not a table.", part)))?;
+        current = obj.entry(part.to_string()).or_insert_with(|| Value::Object(Map::new()));
+    }
+    match current.as_object_mut() {
+        Some(obj) => {
+            obj.insert(last.to_string(), new);
+            Ok(())
+        }
+        None => Err(invalid_data(format!("Cannot set {}.", key))),
+    }
+}
+
+/// Check if `key` is a valid config key. Keys under an empty table in the
+/// default config (such as the key bindings) are free-form.
+fn is_valid_key(key: &str) -> bool {
+    let default = serde_json::to_value(Config::default()).unwrap();
+    let mut current = &default;
+    for part in key.split('.') {
+        match current.get(part) {
+            Some(value) => current = value,
+            None => return current.as_object().is_some_and(|obj| obj.is_empty()),
+        }
+    }
+    true
+}
+
+impl Config {
+    /// The shell used by `call --shell`, such as `sh -c`.
+    pub fn shell_command(&self) -> Vec<String> {
+        match &self.shell {
+            Some(shell) if !shell.is_empty() => shell.clone(),
+            _ if cfg!(target_os = "windows") => vec!["cmd".to_string(), "/C".to_string()],
+            _ => vec!["sh".to_string(), "-c".to_string()],
+        }
+    }
+
+    fn from_value(value: Value) -> std::io::Result<Self> {
+        let config: Self = serde_json::from_value(value)
+            .map_err(|err| invalid_data(format!("Invalid config: {}", err)))?;
+        // the tui keeps at least one line of the history and the scrollback
+        for (key, size) in [("history-size", config.history_size), ("scrollback-size", config.scrollback_size)] {
+            if size < 1 {
+                return Err(invalid_data(format!("Invalid config: {} must be at least 1", key)));
+            }
+        }
+        Ok(config)
+    }
+
+    /// Merge the user config and the project config into a json value.
+    fn load_value() -> std::io::Result<Value> {
+        let mut value = serde_json::to_value(Config::default()).unwrap();
+        if let Some(path) = user_config_path() {
+            merge(&mut value, read_value(&path)?);
+        }
+        merge(&mut value, read_value(&project_config_path()?)?);
+        Ok(value)
+    }
+
+    /// Load the config. Values in the project config take precedence over the
+    /// ones in the user config.
+    pub fn load() -> std::io::Result<Self> {
+        Self::from_value(Self::load_value()?)
+    }
+
+    /// Get the value of a key as a string.
+    pub fn get(key: Option<&str>) -> std::io::Result<String> {
+        let value = Self::load_value()?;
+        let value = match key {
+            Some(key) => get_key(&value, key)
+                .ok_or_else(|| invalid_data(format!("No such config key: {}", key)))?,
+            None => &value,
+        };
+        match value {
+            Value::String(s) => Ok(s.clone()),
+            value => Ok(serde_json::to_string_pretty(value)?),
+        }
+    }
+
+    /// Set the value of a key in the project config, or the user config if `user`
+    /// is true. `value` is parsed as json, or used as a string if it is not a
+    /// valid json.
+    pub fn set(key: &str, value: &str, user: bool) -> std::io::Result<()> {
+        let new = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+        Self::set_value(key, Some(new), user)
+    }
+
+    /// Define an alias in the project config, or remove it if `command` is None.
+    pub fn set_alias(name: &str, command: Option<&str>) -> std::io::Result<()> {
+        let new = command.map(|command| Value::String(command.to_string()));
+        Self::set_value(&format!("aliases.{}", name), new, false)
+    }
+
+    /// Set the value of a key, or remove it if `new` is None.
+    fn set_value(key: &str, new: Option<Value>, user: bool) -> std::io::Result<()> {
+        if !is_valid_key(key) {
+            return Err(invalid_data(format!("No such config key: {}", key)));
+        }
+        let path = if user {
+            user_config_path()
+                .ok_or_else(|| invalid_data("Cannot find the user config directory.".to_string()))?
+        } else {
+            get_vtree_path(true)?.join(_CONFIG)
+        };
+        let mut config = read_value(&path)?;
+        match new {
+            Some(new) => set_key(&mut config, key, new)?,
+            None => remove_key(&mut config, key),
+        }
+
+        // validate the config before saving
+        let mut merged = serde_json::to_value(Config::default()).unwrap();
+        merge(&mut merged, config.clone());
+        let merged = Self::from_value(merged)?;
+        Keymap::from_config(&merged.keymap)?;
+        Palette::from_config(&merged, false)?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(&config)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge() {
+        let mut base = json!({"a": 1, "b": {"c": 2, "d": 3}});
+        merge(&mut base, json!({"b": {"c": 4}, "e": 5}));
+        assert_eq!(base, json!({"a": 1, "b": {"c": 4, "d": 3}, "e": 5}));
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut value = json!({});
+        set_key(&mut value, "colors.error", json!("magenta")).unwrap();
+        assert_eq!(get_key(&value, "colors.error"), Some(&json!("magenta")));
+        assert_eq!(get_key(&value, "colors.text"), None);
+        remove_key(&mut value, "colors.error");
+        remove_key(&mut value, "no.such.key");
+        assert_eq!(value, json!({"colors": {}}));
+
+        assert!(is_valid_key("history-size"));
+        assert!(is_valid_key("colors.error"));
+        assert!(is_valid_key("keymap.bindings.ctrl-k"));
+        assert!(!is_valid_key("history"));
+        assert!(is_valid_key("themes.mine.base"));
+        assert!(!is_valid_key("theme.dark"));
+        assert!(is_valid_key("aliases.plot"));
+    }
+
+    #[test]
+    fn test_from_value() {
+        let mut value = serde_json::to_value(Config::default()).unwrap();
+        merge(&mut value, json!({"history-size": 10, "shell": ["bash", "-c"]}));
+        let config = Config::from_value(value).unwrap();
+        assert_eq!(config.history_size, 10);
+        assert_eq!(config.shell, Some(vec!["bash".to_string(), "-c".to_string()]));
+        assert_eq!(config.scrollback_size, 1000);
+        assert!(Config::from_value(json!({"history-size": "many"})).is_err());
+        for key in ["history-size", "scrollback-size"] {
+            let mut value = serde_json::to_value(Config::default()).unwrap();
+            merge(&mut value, json!({key: 0}));
+            assert!(Config::from_value(value).is_err());
+        }
+    }
+}
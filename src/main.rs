@@ -1,10 +1,13 @@
 pub mod tree;
 pub mod terminal;
 pub mod vtui;
-use std::{fs::File, io::Write, path::PathBuf};
-use structopt::StructOpt;
-use tree::TreeItem;
-use vtui::enter;
+pub mod config;
+pub mod completion;
+pub mod trash;
+use std::{collections::HashMap, fs::File, io::Write, path::PathBuf};
+use structopt::{StructOpt, clap::{AppSettings, Shell}};
+use tree::{TreeItem, TreeModel, core::unique_path, tree_item::is_valid_item_name};
+use vtui::{enter, browse, session::remove_item};
 
 
 // The main command line interface for vtree.
@@ -15,12 +18,92 @@ enum VTree {
     New {name: Option<String>},  // vtree new {name}: create a new virtual directory.
     Tree {name: String},  // vtree tree {name}: show the virtual directory tree.
     Enter {name: String},  // vtree enter {name}: enter the virtual directory.
+    Browse {name: String},  // vtree browse {name}: browse the virtual directory tree.
     List {contains: Option<String>},  // vtree list: show all the names of virtual root trees.
     Remove {
         name: String,
         #[structopt(long)]
         dry: bool,
-    },  // vtree remove {name}: remove a virtual root tree.
+        #[structopt(short="y", long="yes", about="Do not ask for confirmation")]
+        yes: bool,
+    },  // vtree remove {name}: move a virtual root tree to the trash.
+    Restore {name: String},  // vtree restore {name}: restore the latest removed tree with the name.
+    Trash {
+        #[structopt(subcommand)]
+        cmd: TrashCommand,
+    },  // vtree trash list/empty: show or delete the removed trees.
+    Config {
+        #[structopt(subcommand)]
+        cmd: ConfigCommand,
+    },  // vtree config get/set: show or edit the configuration.
+    Add {tree: String, realpath: PathBuf, vpath: Option<String>},  // vtree add {tree} {realpath} {vpath}: add a file to the tree.
+    Mkdir {tree: String, vpath: String},  // vtree mkdir {tree} {vpath}: make a virtual directory.
+    Rm {tree: String, vpath: String},  // vtree rm {tree} {vpath}: remove an item from the tree.
+    Mv {tree: String, src: String, dst: String},  // vtree mv {tree} {src} {dst}: move an item in the tree.
+    Ls {
+        tree: String,
+        vpath: Option<String>,
+        #[structopt(short="d", long="desc", about="Show descriptions")]
+        desc: bool,
+    },  // vtree ls {tree} {vpath}: list the items in a virtual directory.
+    Cat {tree: String, vpath: String},  // vtree cat {tree} {vpath}: print the content of a file.
+    Desc {tree: String, vpath: String, text: Option<String>},  // vtree desc {tree} {vpath} {text}: show or set the description.
+    Resolve {path: String},  // vtree resolve {tree}/{vpath}: print the real path of a file.
+    Paths {
+        path: String,
+        #[structopt(short="0", long="null", about="Separate the paths with NUL characters")]
+        null: bool,
+        #[structopt(long, conflicts_with="existing", about="Only show paths that do not exist")]
+        missing: bool,
+        #[structopt(long, about="Only show paths that exist")]
+        existing: bool,
+    },  // vtree paths {tree}/{vpath}: print the real paths of all the files under a directory.
+    Rename {old: String, new: String},  // vtree rename {old} {new}: rename a virtual root tree.
+    Copy {
+        src: String,
+        dst: String,
+        #[structopt(short="f", long="files", about="Also copy the virtual files")]
+        files: bool,
+    },  // vtree copy {src} {dst}: copy a virtual root tree.
+    Describe {name: String, text: String},  // vtree describe {name} {text}: set the description of a virtual root tree.
+    Completions {
+        #[structopt(possible_values = &["bash", "zsh", "fish"])]
+        shell: Shell,
+    },  // vtree completions {shell}: print the shell completion script.
+    #[structopt(
+        setting = AppSettings::Hidden,
+        setting = AppSettings::TrailingVarArg,
+        setting = AppSettings::AllowLeadingHyphen,
+    )]
+    Complete {index: usize, words: Vec<String>},  // vtree complete {index} {words}: print the completion candidates.
+}
+
+// Subcommands of vtree config.
+#[derive(StructOpt)]
+enum ConfigCommand {
+    #[structopt(about = "Show the value of a config key, or the whole config")]
+    Get {key: Option<String>},
+    #[structopt(about = "Set the value of a config key in .vtree/config")]
+    Set {
+        key: String,
+        value: String,
+        #[structopt(long, help = "Set the value in the user config instead")]
+        user: bool,
+    },
+}
+
+// Subcommands of vtree trash.
+#[derive(StructOpt)]
+enum TrashCommand {
+    #[structopt(about = "Show the removed trees")]
+    List,
+    #[structopt(about = "Delete the removed trees permanently")]
+    Empty {
+        #[structopt(long, help = "Only delete the trees removed before this period, such as 30d or 12h")]
+        older_than: Option<String>,
+        #[structopt(short="y", long="yes", help = "Do not ask for confirmation")]
+        yes: bool,
+    },
 }
 
 // Subdirectory names used in vtree
@@ -63,6 +146,185 @@ pub fn get_json_path(name: &String) -> std::io::Result<PathBuf> {
     Ok(path.join(_TREES).join(format!("{}.json", name)))
 }
 
+/// Load the virtual tree with the given name.
+/// # Errors
+/// If .vtree directory or the tree does not exist, return an error.
+pub fn load_tree(name: &str) -> std::io::Result<TreeModel> {
+    let path = get_json_path(&name.to_string())?;
+    if !path.exists() {
+        return Err(
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Virtual directory {} does not exist.", name),
+            )
+        );
+    }
+    TreeModel::from_file(&path)
+}
+
+/// Load the virtual tree with the given name, apply `f` to it and save it.
+fn edit_tree<F>(name: &str, f: F) -> std::io::Result<()>
+where
+    F: FnOnce(&mut TreeModel) -> tree::error::Result<()>,
+{
+    let mut tree = load_tree(name)?;
+    f(&mut tree)?;
+    tree.to_file(&get_json_path(&name.to_string())?)
+}
+
+/// Print the items in the directory `vpath` of the tree.
+fn ls(name: &str, vpath: Option<String>, desc: bool) -> std::io::Result<()> {
+    let tree = load_tree(name)?;
+    let text = if desc { tree.ls_detailed(vpath)? } else { tree.ls_simple(vpath)? };
+    println!("{}", text);
+    Ok(())
+}
+
+/// Print the content of the file `vpath` of the tree.
+fn cat(name: &str, vpath: &String) -> std::io::Result<()> {
+    print!("{}", load_tree(name)?.read_file(vpath)?);
+    Ok(())
+}
+
+/// Set the description of the item `vpath`, or print it if `text` is None.
+fn desc(name: &str, vpath: &String, text: Option<String>) -> std::io::Result<()> {
+    match text {
+        Some(text) => edit_tree(name, |tree| tree.set_description(vpath, Some(text))),
+        None => {
+            if let Some(desc) = &load_tree(name)?.get_item(vpath)?.desc {
+                println!("{}", desc);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Print the real path of a path such as "tree/a/b".
+fn resolve(path: &str) -> std::io::Result<()> {
+    let (name, vpath) = split_tree_path(path);
+    println!("{}", load_tree(&name)?.entity_abspath(&vpath)?.display());
+    Ok(())
+}
+
+/// Print the error of a command and exit with status 1, so that the scripts
+/// driving vtree can tell that the command failed.
+fn exit_on_error<T>(result: std::io::Result<T>) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("vtree: {}", err);
+        std::process::exit(1);
+    })
+}
+
+/// Split a path such as "tree/a/b" into the tree name and the virtual path "~/a/b".
+fn split_tree_path(path: &str) -> (String, String) {
+    match path.trim_start_matches('/').split_once('/') {
+        Some((tree, vpath)) => (tree.to_string(), format!("~/{}", vpath)),
+        None => (path.trim_start_matches('/').to_string(), "~".to_string()),
+    }
+}
+
+/// Print the real paths of all the entities under `path`.
+fn paths(path: &str, null: bool, missing: bool, existing: bool) -> std::io::Result<()> {
+    let (name, vpath) = split_tree_path(path);
+    let tree = load_tree(&name)?;
+    let separator = if null { '\0' } else { '\n' };
+    let mut stdout = std::io::stdout().lock();
+    for path in tree.entity_abspaths(&vpath)? {
+        if (missing && path.exists()) || (existing && !path.exists()) {
+            continue;
+        }
+        write!(stdout, "{}{}", path.display(), separator)?;
+    }
+    stdout.flush()
+}
+
+/// True if `name` can be used as a tree name. It is also a file name in .vtree/trees.
+fn is_valid_tree_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && is_valid_item_name(name)
+}
+
+/// Check that `name` can be used as the name of a new tree and return the path
+/// of its json file.
+fn new_tree_path(name: &str) -> std::io::Result<PathBuf> {
+    if !is_valid_tree_name(name) {
+        return Err(
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid tree name: {}", name),
+            )
+        );
+    }
+    let path = get_json_path(&name.to_string())?;
+    if path.exists() {
+        return Err(
+            std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("Virtual directory {} already exists.", name),
+            )
+        );
+    }
+    Ok(path)
+}
+
+/// Path of the directory of the virtual files, .vtree/virtual-files.
+fn get_virtual_files_path() -> std::io::Result<PathBuf> {
+    Ok(get_vtree_path(true)?.join(_VIRTUAL_FILES))
+}
+
+/// True if `path` is a file created by vtree in `vfiles`, the directory of the
+/// virtual files. A relative path is relative to the current directory.
+fn is_virtual_file(path: &std::path::Path, vfiles: &std::path::Path) -> bool {
+    match std::env::current_dir() {
+        Ok(cwd) => cwd.join(path).starts_with(vfiles),
+        Err(_) => path.starts_with(vfiles),
+    }
+}
+
+/// Copy the virtual files in `vfiles` used by `item` and its descendants, and let
+/// the items refer to the copies. `copies` maps the copied files to their copies,
+/// so that items sharing a file also share the copy.
+fn copy_virtual_files(
+    item: &mut TreeItem, vfiles: &std::path::Path, copies: &mut HashMap<PathBuf, PathBuf>
+) -> std::io::Result<()> {
+    if let Some(path) = item.entity.clone().filter(|path| is_virtual_file(path, vfiles)) {
+        let copy = match copies.get(&path) {
+            Some(copy) => copy.clone(),
+            None => {
+                let copy = unique_path(path.clone())?;
+                std::fs::copy(&path, &copy)?;
+                copies.insert(path, copy.clone());
+                copy
+            }
+        };
+        item.entity = Some(copy);
+    }
+    for child in item.iter_children_mut() {
+        copy_virtual_files(child, vfiles, copies)?;
+    }
+    Ok(())
+}
+
+/// Rename the tree `old` to `new`.
+fn rename(old: &str, new: &str) -> std::io::Result<()> {
+    let mut tree = load_tree(old)?;
+    let path = new_tree_path(new)?;
+    tree.root.name = new.to_string();
+    tree.to_file(&path)?;
+    std::fs::remove_file(get_json_path(&old.to_string())?)
+}
+
+/// Copy the tree `src` to `dst`. If `files` is true, the virtual files are also
+/// copied so that editing them does not affect the original tree.
+fn copy(src: &str, dst: &str, files: bool) -> std::io::Result<()> {
+    let mut tree = load_tree(src)?;
+    let path = new_tree_path(dst)?;
+    tree.root.name = dst.to_string();
+    if files {
+        copy_virtual_files(&mut tree.root, &get_virtual_files_path()?, &mut HashMap::new())?;
+    }
+    tree.to_file(&path)
+}
+
 /// Initialize current directory with vtree metadata.
 /// This command is the first one to run before using vtree.
 fn init() -> std::io::Result<()>{
@@ -126,6 +388,9 @@ fn list(contains: Option<String>) -> std::io::Result<()> {
     for entry in std::fs::read_dir(path)? {
         let entry = entry?;
         let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;  // skip temporary files
+        }
         if path.is_file() {
             if contains == "" || path.file_name().unwrap().to_str().unwrap().contains(&contains){
                 let item = tree::TreeItem::from_file(&path)?;
@@ -139,7 +404,39 @@ fn list(contains: Option<String>) -> std::io::Result<()> {
     Ok(())
 }
 
-fn remove(name: String, dry: bool) -> std::io::Result<()> {
+/// Ask the user to confirm an action. Returns an error unless the answer is "y"
+/// or "yes", so that a script without `--yes` does not assume that the action
+/// was done.
+fn confirm(prompt: &str) -> std::io::Result<()> {
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+    let answer = terminal::input::input()?;
+    if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        Ok(())
+    } else {
+        Err(std::io::Error::new(std::io::ErrorKind::Interrupted, "Canceled."))
+    }
+}
+
+/// Canonical paths of the virtual files used by the trees other than the one at
+/// `json`.
+fn files_used_by_others(json: &std::path::Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(get_vtree_path(true)?.join(_TREES))? {
+        let path = entry?.path();
+        if path == json || path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        for item in TreeItem::from_file(&path)?.entities() {
+            if let Some(file) = item.entity.as_ref().and_then(|file| file.canonicalize().ok()) {
+                files.push(file);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn remove(name: String, dry: bool, yes: bool) -> std::io::Result<()> {
     let path = get_json_path(&name)?;
     if !path.exists() {
         return Err(
@@ -151,28 +448,89 @@ fn remove(name: String, dry: bool) -> std::io::Result<()> {
     }
     // let tree = tree::TreeModel::from_file(&path)?;
     let tree = TreeItem::from_file(&path)?;
-    let default = std::path::Path::new("");
 
+    // virtual files that other trees still use, such as the ones shared by
+    // `vtree copy`, are kept
+    let used = files_used_by_others(&path)?;
+    let vfiles = get_virtual_files_path()?;
+    let mut files: Vec<PathBuf> = Vec::new();
     for item in &tree.entities() {
         if let Some(path) = &item.entity {
-            if path.parent().unwrap_or(default).ends_with(_VIRTUAL_FILES) {
-                if dry {
-                    println!("Remove: {}", path.display());
-                }
-                else {
-                    std::fs::remove_file(path).unwrap_or(());
-                }
+            let shared = path.canonicalize().is_ok_and(|path| used.contains(&path));
+            if is_virtual_file(path, &vfiles) && !shared && !files.contains(path) {
+                files.push(path.clone());
             }
         }
     };
-    
+
     if dry {
-        println!("Remove: {}", path.display());
+        for file in &files {
+            println!("Move to trash: {}", file.display());
+        }
+        println!("Move to trash: {}", path.display());
+        return Ok(());
     }
-    else {
-        std::fs::remove_file(path)?;
+    if !yes {
+        confirm(&format!("Remove {} and its {} virtual files?", name, files.len()))?;
     }
-    
+    let dir = trash::move_to_trash(&trash::trash_path()?, &path, &files)?;
+    println!("Moved {} to {}", name, dir.display());
+    Ok(())
+}
+
+/// Restore the latest removed tree with the given name from the trash.
+fn restore(name: String) -> std::io::Result<()> {
+    let entry = trash::entries(&trash::trash_path()?)?
+        .into_iter()
+        .rev()
+        .find(|entry| entry.name == name)
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} is not in the trash.", name),
+            )
+        })?;
+    trash::restore(&entry, &new_tree_path(&name)?, &get_virtual_files_path()?)?;
+    println!("Restored {}", name);
+    Ok(())
+}
+
+fn trash_list() -> std::io::Result<()> {
+    for entry in trash::entries(&trash::trash_path()?)? {
+        println!(
+            "{} ({}, {} virtual files)",
+            entry.name,
+            trash::format_age(entry.removed),
+            entry.file_count(),
+        );
+    }
+    Ok(())
+}
+
+fn trash_empty(older_than: Option<String>, yes: bool) -> std::io::Result<()> {
+    let older_than = match older_than {
+        Some(s) => Some(trash::parse_duration(&s).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid duration: {}", s),
+            )
+        })?),
+        None => None,
+    };
+    let entries = trash::expired(&trash::trash_path()?, older_than)?;
+    if entries.is_empty() {
+        println!("Nothing to delete.");
+        return Ok(());
+    }
+    if !yes {
+        confirm(&format!("Permanently delete {} removed trees?", entries.len()))?;
+    }
+    for entry in &entries {
+        if entry.path.exists() {
+            std::fs::remove_dir_all(&entry.path)?;
+        }
+    }
+    println!("Deleted {} removed trees.", entries.len());
     Ok(())
 }
 
@@ -195,11 +553,145 @@ fn main() {
         VTree::Enter { name } => {
             enter(name).unwrap();
         }
+        VTree::Browse { name } => {
+            browse(name).unwrap();
+        }
         VTree::List { contains } => {
             list(contains).unwrap();
         }
-        VTree::Remove { name, dry } => {
-            remove(name, dry).unwrap();
+        VTree::Remove { name, dry, yes } => {
+            exit_on_error(remove(name, dry, yes));
+        }
+        VTree::Restore { name } => {
+            exit_on_error(restore(name));
+        }
+        VTree::Trash { cmd } => {
+            match cmd {
+                TrashCommand::List => {
+                    exit_on_error(trash_list());
+                }
+                TrashCommand::Empty { older_than, yes } => {
+                    exit_on_error(trash_empty(older_than, yes));
+                }
+            }
+        }
+        VTree::Config { cmd } => {
+            match cmd {
+                ConfigCommand::Get { key } => {
+                    println!("{}", exit_on_error(config::Config::get(key.as_deref())));
+                }
+                ConfigCommand::Set { key, value, user } => {
+                    exit_on_error(config::Config::set(&key, &value, user));
+                }
+            }
+        }
+        VTree::Add { tree, realpath, vpath } => {
+            exit_on_error(edit_tree(&tree, |tree| tree.add_alias(vpath.as_ref(), realpath)));
+        }
+        VTree::Mkdir { tree, vpath } => {
+            exit_on_error(edit_tree(&tree, |tree| tree.make_directory(&vpath)));
+        }
+        VTree::Rm { tree, vpath } => {
+            exit_on_error(edit_tree(&tree, |tree| remove_item(tree, &vpath)));
+        }
+        VTree::Mv { tree, src, dst } => {
+            exit_on_error(edit_tree(&tree, |tree| tree.move_child(&src, &dst)));
+        }
+        VTree::Ls { tree, vpath, desc } => {
+            exit_on_error(ls(&tree, vpath, desc));
+        }
+        VTree::Cat { tree, vpath } => {
+            exit_on_error(cat(&tree, &vpath));
+        }
+        VTree::Resolve { path } => {
+            exit_on_error(resolve(&path));
+        }
+        VTree::Paths { path, null, missing, existing } => {
+            exit_on_error(paths(&path, null, missing, existing));
+        }
+        VTree::Rename { old, new } => {
+            exit_on_error(rename(&old, &new));
+        }
+        VTree::Copy { src, dst, files } => {
+            exit_on_error(copy(&src, &dst, files));
+        }
+        VTree::Describe { name, text } => {
+            let desc = if text.is_empty() { None } else { Some(text) };
+            exit_on_error(edit_tree(&name, |tree| tree.set_description(&"~".to_string(), desc)));
+        }
+        VTree::Completions { shell } => {
+            exit_on_error(completion::generate(VTree::clap(), shell, &mut std::io::stdout()));
+        }
+        VTree::Complete { index, words } => {
+            if !completion::complete(index, &words).unwrap_or(false) {
+                std::process::exit(1);
+            }
+        }
+        VTree::Desc { tree, vpath, text } => {
+            exit_on_error(desc(&tree, &vpath, text));
         }
     };
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_tree_name() {
+        assert!(is_valid_tree_name("Project_A"));
+        assert!(is_valid_tree_name("run-1.2"));
+        assert!(!is_valid_tree_name(""));
+        assert!(!is_valid_tree_name(".hidden"));
+        assert!(!is_valid_tree_name("a/b"));
+        assert!(!is_valid_tree_name("a:b"));
+    }
+
+    #[test]
+    fn test_is_virtual_file() {
+        let cwd = std::env::current_dir().unwrap();
+        let vfiles = cwd.join(".vtree").join(_VIRTUAL_FILES);
+        assert!(is_virtual_file(&vfiles.join("a.txt"), &vfiles));
+        assert!(is_virtual_file(std::path::Path::new("./.vtree/virtual-files/a.txt"), &vfiles));
+        assert!(!is_virtual_file(&cwd.join("project").join(_VIRTUAL_FILES).join("a.txt"), &vfiles));
+        assert!(!is_virtual_file(std::path::Path::new("virtual-files/a.txt"), &vfiles));
+    }
+
+    #[test]
+    fn test_copy_virtual_files() {
+        let dir = std::env::current_dir().unwrap().join("target/test-copy-virtual-files");
+        let vfiles = dir.join(".vtree").join(_VIRTUAL_FILES);
+        let other = dir.join("project").join(_VIRTUAL_FILES);
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        std::fs::create_dir_all(&vfiles).unwrap();
+        std::fs::create_dir_all(&other).unwrap();
+        std::fs::write(vfiles.join("a.txt"), "a").unwrap();
+        std::fs::write(other.join("data.csv"), "data").unwrap();
+
+        // two items share a.txt, and data.csv is a file of the user in a
+        // directory also named virtual-files
+        let entity = |name: &str| format!("{:?}", name);
+        let json = format!(
+            r#"{{"name": "t", "children": [
+                {{"name": "a1", "children": [], "entity": {}}},
+                {{"name": "a2", "children": [], "entity": {}}},
+                {{"name": "data", "children": [], "entity": {}}}
+            ]}}"#,
+            entity(vfiles.join("a.txt").to_str().unwrap()),
+            entity(vfiles.join("a.txt").to_str().unwrap()),
+            entity(other.join("data.csv").to_str().unwrap()),
+        );
+        let mut tree = TreeModel::from_string(&json);
+        copy_virtual_files(&mut tree.root, &vfiles, &mut HashMap::new()).unwrap();
+        let entity_of = |name: &str| tree.entity_abspath(&name.to_string()).unwrap();
+        assert_eq!(entity_of("a1"), vfiles.join("a-0.txt"));
+        assert_eq!(entity_of("a2"), vfiles.join("a-0.txt"));
+        assert_eq!(entity_of("data"), other.join("data.csv"));
+        assert_eq!(std::fs::read_to_string(vfiles.join("a-0.txt")).unwrap(), "a");
+        assert_eq!(std::fs::read_dir(&vfiles).unwrap().count(), 2);
+        assert_eq!(std::fs::read_dir(&other).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
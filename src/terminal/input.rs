@@ -1,6 +1,6 @@
-use std::io::Write;
-use structopt::StructOpt;
-use super::parser::parse_string;
+use std::{collections::HashMap, io::Write, path::PathBuf};
+use structopt::{StructOpt, clap::{self, AppSettings}};
+use super::parser::{parse_string, split_pipeline, expand_aliases};
 
 pub fn input() -> std::io::Result<String> {
     let mut val = String::new();
@@ -23,17 +23,62 @@ pub enum VCommand {
     Mkdir {name: String},
     Rm {name: String},
     Pwd,
-    Cat {name: String},
+    Cat {
+        name: String,
+        #[structopt(long="plain", about="Print without syntax highlighting")]
+        plain: bool,
+        #[structopt(short="n", long="number", about="Show line numbers")]
+        number: bool,
+    },
     Touch {name: String},
     Open {name: String},
+    #[structopt(about = "Edit a file with $VISUAL or $EDITOR")]
+    Edit {name: Option<String>},
     Cp {src: String, dst: Option<String>},
-    Call {vec: Vec<String>},
+    #[structopt(setting = AppSettings::TrailingVarArg)]
+    Call {
+        #[structopt(short="i", long="interactive", about="Hand the terminal over to the command")]
+        interactive: bool,
+        #[structopt(short="s", long="shell", about="Run the command line in the shell")]
+        shell: bool,
+        #[structopt(long="no-resolve", about="Substitute only the placeholders such as {a.txt}")]
+        no_resolve: bool,
+        vec: Vec<String>,
+    },
+    #[structopt(
+        setting = AppSettings::TrailingVarArg,
+        about = "Run `call` for each file matching a pattern, such as `foreach *.csv call wc {}`",
+    )]
+    Foreach {
+        #[structopt(short="j", long="jobs", default_value="1", about="Number of commands run in parallel")]
+        jobs: usize,
+        pattern: String,
+        vec: Vec<String>,
+    },
+    #[structopt(about = "List the background jobs")]
+    Jobs,
+    #[structopt(about = "Show the output of a background job, such as `fg %1`, and wait for it")]
+    Fg {job: Option<String>},
+    #[structopt(about = "Kill a background job, such as `kill %1`")]
+    Kill {job: String},
+    #[structopt(about = "Set a session variable as NAME=value, or list the variables")]
+    Set {assignment: Option<String>},
+    #[structopt(
+        setting = AppSettings::TrailingVarArg,
+        about = "Define an alias as name='command', or list the aliases",
+    )]
+    Alias {definition: Vec<String>},
+    #[structopt(about = "Remove an alias")]
+    Unalias {name: String},
     Desc {
         name: Option<String>, 
         #[structopt(short="d", long="desc", about = "Descriptions")]
         desc: Option<String>,
     },
     Mv {src: String, dst: String},
+    SaveOutput {file: PathBuf},
+    #[structopt(about = "Run the commands in a script file")]
+    Source {file: PathBuf},
     Exit {
         #[structopt(long="discard", about="Discard changes and exit")]
         discard: bool,
@@ -61,3 +106,137 @@ impl VCommand {
         Self::from_string(&val)
     }
 }
+
+/// Target of an output redirection.
+pub struct Redirect {
+    pub target: String,  // Virtual path of the file.
+    pub append: bool,  // True for `>>`.
+}
+
+/// Virtual commands connected with `|`. The output of the last command may be
+/// redirected to a file with `>` or `>>`, and a trailing `&` runs the commands in
+/// the background.
+pub struct Pipeline {
+    pub commands: Vec<VCommand>,
+    pub redirect: Option<Redirect>,
+    pub background: bool,
+}
+
+fn syntax_error(msg: String) -> clap::Error {
+    clap::Error {
+        message: format!("error: {}", msg),
+        kind: clap::ErrorKind::InvalidValue,
+        info: None,
+    }
+}
+
+impl Pipeline {
+    /// Parse a command line after expanding the aliases.
+    pub fn from_string(
+        val: &str, aliases: &HashMap<String, String>
+    ) -> std::result::Result<Self, clap::Error> {
+        let mut parts = split_pipeline(&expand_aliases(val, aliases)).into_iter();
+        let mut lines = vec![parts.next().unwrap_or_default()];
+        let mut redirect = None;
+        let mut background = false;
+        while let (Some(op), Some(text)) = (parts.next(), parts.next()) {
+            if background {
+                return Err(syntax_error("Unexpected & before the end of the line.".to_string()));
+            }
+            if op == "&" {
+                background = true;
+                if !text.trim().is_empty() {
+                    return Err(syntax_error("Unexpected & before the end of the line.".to_string()));
+                }
+                continue;
+            }
+            if redirect.is_some() {
+                return Err(syntax_error(format!("Unexpected {} after the redirection.", op)));
+            }
+            if op == "|" {
+                lines.push(text);
+                continue;
+            }
+            let target = match parse_string(&text).as_slice() {
+                [target] => target.clone(),
+                _ => return Err(syntax_error(format!("Expected a file name after {}.", op))),
+            };
+            redirect = Some(Redirect { target, append: op == ">>" });
+        }
+        let single = lines.len() == 1 && redirect.is_none() && !background;
+        if !single && lines.iter().any(|line| line.trim().is_empty()) {
+            return Err(syntax_error("Missing command in the pipeline.".to_string()));
+        }
+        let commands = lines
+            .iter()
+            .map(VCommand::from_string)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(Self { commands, redirect, background })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_call_arguments() {
+        match VCommand::from_string(&"call ls -l \"a b\"".to_string()).unwrap() {
+            VCommand::Call { interactive, shell, vec, .. } => {
+                assert!(!interactive && !shell);
+                assert_eq!(vec, vec!["ls", "-l", "a b"]);
+            }
+            _ => panic!("not a call command"),
+        }
+        match VCommand::from_string(&"call -i --shell vim -n".to_string()).unwrap() {
+            VCommand::Call { interactive, shell, vec, .. } => {
+                assert!(interactive && shell);
+                assert_eq!(vec, vec!["vim", "-n"]);
+            }
+            _ => panic!("not a call command"),
+        }
+    }
+
+    #[test]
+    fn test_foreach_arguments() {
+        match VCommand::from_string(&"foreach -j 4 data/*.csv call --shell wc -l {}".to_string()).unwrap() {
+            VCommand::Foreach { jobs, pattern, vec } => {
+                assert_eq!(jobs, 4);
+                assert_eq!(pattern, "data/*.csv");
+                assert_eq!(vec, vec!["call", "--shell", "wc", "-l", "{}"]);
+            }
+            _ => panic!("not a foreach command"),
+        }
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let pipeline = Pipeline::from_string("cat a.txt | call grep foo > \"b c.txt\"", &HashMap::new()).unwrap();
+        assert_eq!(pipeline.commands.len(), 2);
+        assert!(matches!(pipeline.commands[0], VCommand::Cat { .. }));
+        let redirect = pipeline.redirect.unwrap();
+        assert_eq!(redirect.target, "b c.txt");
+        assert!(!redirect.append);
+
+        let pipeline = Pipeline::from_string("ls >> out.txt", &HashMap::new()).unwrap();
+        assert!(pipeline.redirect.unwrap().append);
+        let pipeline = Pipeline::from_string("call echo '|'", &HashMap::new()).unwrap();
+        assert_eq!(pipeline.commands.len(), 1);
+        assert!(pipeline.redirect.is_none());
+        assert!(!pipeline.background);
+        let pipeline = Pipeline::from_string("call make > log.txt &", &HashMap::new()).unwrap();
+        assert!(pipeline.background && pipeline.redirect.is_some());
+
+        let mut aliases = HashMap::new();
+        aliases.insert("g".to_string(), "call grep $1 | call sort".to_string());
+        let pipeline = Pipeline::from_string("g foo", &aliases).unwrap();
+        assert_eq!(pipeline.commands.len(), 2);
+    }
+
+    #[test]
+    fn test_pipeline_syntax_error() {
+        for line in ["ls |", "| ls", "ls >", "ls > a b", "ls > a | cat b", "> a", "&", "ls & ls", "ls &&"] {
+            assert!(Pipeline::from_string(line, &HashMap::new()).is_err(), "{}", line);
+        }
+    }
+}
@@ -1,4 +1,4 @@
 pub mod input;
 pub mod parser;
-pub use self::input::VCommand;
-pub use self::parser::{parse_string, parse_string_raw};
+pub use self::input::{VCommand, Pipeline, Redirect};
+pub use self::parser::{parse_string, parse_string_raw, split_pipeline, expand_aliases, script_lines};
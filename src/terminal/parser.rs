@@ -1,4 +1,5 @@
 use std::cell::RefCell;
+use std::collections::HashMap;
 
 /// Parse a string into a vector of strings in a CLI-like manner.
 /// # Example
@@ -112,6 +113,206 @@ pub fn parse_string_raw(str: &String) -> Vec<String> {
     inputs
 }
 
+/// Split a string at unquoted `|`, `>`, `>>` and `&`. The operators are returned
+/// as separate elements between the parts of the string. The rest of the line
+/// after `call --shell` is left to the shell, except a trailing `&` that runs the
+/// command in the background.
+/// # Example
+/// ```
+/// split_pipeline("cat a | call wc > b"); // returns ["cat a ", "|", " call wc ", ">", " b"]
+/// split_pipeline("call echo \"a|b\""); // returns ["call echo \"a|b\""]
+/// split_pipeline("call --shell ls | wc &"); // returns ["call --shell ls | wc ", "&", ""]
+/// ```
+pub fn split_pipeline(str: &str) -> Vec<String> {
+    let mut parts: Vec<String> = Vec::new();
+    let mut buf = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = str.chars().peekable();
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) => {
+                if q == c {
+                    quote = None;
+                }
+                buf.push(c);
+            }
+            None => match c {
+                '"' | '\'' => {
+                    quote = Some(c);
+                    buf.push(c);
+                }
+                '|' | '&' | '>' if is_shell_call(&buf) => {
+                    buf.push(c);
+                    buf.extend(chars.by_ref());
+                    let trimmed = buf.trim_end();
+                    if trimmed.ends_with('&') && !trimmed.ends_with("&&") {
+                        buf.truncate(trimmed.len() - 1);
+                        parts.push(std::mem::take(&mut buf));
+                        parts.push("&".to_string());
+                    }
+                }
+                '|' | '&' => {
+                    parts.push(std::mem::take(&mut buf));
+                    parts.push(c.to_string());
+                }
+                '>' => {
+                    parts.push(std::mem::take(&mut buf));
+                    if chars.peek() == Some(&'>') {
+                        chars.next();
+                        parts.push(">>".to_string());
+                    } else {
+                        parts.push(">".to_string());
+                    }
+                }
+                c => buf.push(c),
+            },
+        }
+    }
+    parts.push(buf);
+    parts
+}
+
+/// True if `stage` is a `call` command with the `--shell` or `-s` option.
+fn is_shell_call(stage: &str) -> bool {
+    let mut words = stage.split_whitespace();
+    words.next() == Some("call")
+        && words
+            .take_while(|word| word.starts_with('-'))
+            .any(|word| word == "--shell" || (!word.starts_with("--") && word.contains('s')))
+}
+
+/// Quote an argument if it is not read as a single argument by `parse_string`.
+fn quote_argument(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '"', '\'', '|', '>']) {
+        arg.to_string()
+    } else if arg.contains('"') {
+        format!("'{}'", arg)
+    } else {
+        format!("\"{}\"", arg)
+    }
+}
+
+/// Substitute `$1`, `$2`, ... in an alias with the arguments and `$@` with all of
+/// them. If the alias uses none of them, the arguments are appended.
+fn substitute_arguments(alias: &str, args: &[String]) -> String {
+    let mut out = String::new();
+    let mut used = false;
+    let mut rest = alias;
+    while let Some(idx) = rest.find('$') {
+        out.push_str(&rest[..idx]);
+        let tail = &rest[idx + 1..];
+        let ndigits = tail.chars().take_while(|c| c.is_ascii_digit()).count();
+        let num = tail[..ndigits].parse::<usize>().unwrap_or(0);
+        if let Some(tail) = tail.strip_prefix('@') {
+            out.push_str(&args.join(" "));
+            used = true;
+            rest = tail;
+        } else if num > 0 {
+            out.push_str(args.get(num - 1).map(|arg| arg.as_str()).unwrap_or(""));
+            used = true;
+            rest = &tail[ndigits..];
+        } else {
+            out.push('$');
+            rest = tail;
+        }
+    }
+    out.push_str(rest);
+    if !used && !args.is_empty() {
+        out.push(' ');
+        out.push_str(&args.join(" "));
+    }
+    out
+}
+
+/// Expand the alias at the beginning of a command. The expanded command is
+/// expanded again unless it starts with an alias that is already expanded.
+fn expand_alias(command: &str, aliases: &HashMap<String, String>) -> String {
+    let mut command = command.to_string();
+    let mut expanded: Vec<String> = Vec::new();
+    loop {
+        let args = parse_string(&command);
+        let alias = match args.first() {
+            Some(name) if !expanded.contains(name) => match aliases.get(name) {
+                Some(alias) => alias,
+                None => break,
+            },
+            _ => break,
+        };
+        let rest: Vec<String> = args[1..].iter().map(|arg| quote_argument(arg)).collect();
+        command = substitute_arguments(alias, &rest);
+        expanded.push(args[0].clone());
+    }
+    command
+}
+
+/// Expand the aliases at the beginning of each command in a pipeline.
+/// # Example
+/// ```
+/// // with an alias "g" for "call grep"
+/// expand_aliases("cat a | g foo", &aliases); // returns "cat a |call grep foo"
+/// ```
+pub fn expand_aliases(str: &str, aliases: &HashMap<String, String>) -> String {
+    let parts = split_pipeline(str);
+    let mut out = String::new();
+    for (idx, part) in parts.iter().enumerate() {
+        if idx % 2 == 0 && (idx == 0 || parts[idx - 1] == "|") {
+            out.push_str(&expand_alias(part, aliases));
+        } else {
+            out.push_str(part);
+        }
+    }
+    out
+}
+
+/// Remove a comment from a line. A comment starts with `#` that is not quoted and
+/// not in the middle of a word.
+fn strip_comment(line: &str) -> &str {
+    let mut quote: Option<char> = None;
+    let mut prev = ' ';
+    for (idx, c) in line.char_indices() {
+        match quote {
+            Some(q) if q == c => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && prev.is_whitespace() => return &line[..idx],
+            None => {}
+        }
+        prev = c;
+    }
+    line
+}
+
+/// Split a script into commands with the line numbers where they start. Comments
+/// are removed, and lines ending with `\` are continued to the next line.
+/// # Example
+/// ```
+/// script_lines("# comment\nmkdir a \\\n  b\n"); // returns [(2, "mkdir a   b")]
+/// ```
+pub fn script_lines(text: &str) -> Vec<(usize, String)> {
+    let mut commands: Vec<(usize, String)> = Vec::new();
+    let mut buf = String::new();
+    let mut start: Option<usize> = None;
+    for (idx, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim_end();
+        let lineno = *start.get_or_insert(idx + 1);
+        match line.strip_suffix('\\') {
+            Some(line) => buf.push_str(line),
+            None => {
+                buf.push_str(line);
+                if !buf.trim().is_empty() {
+                    commands.push((lineno, buf.trim().to_string()));
+                }
+                buf.clear();
+                start = None;
+            }
+        }
+    }
+    if let Some(lineno) = start.filter(|_| !buf.trim().is_empty()) {
+        commands.push((lineno, buf.trim().to_string()));
+    }
+    commands
+}
+
 #[cfg(test)]
 mod test_parse_string {
     use super::*;
@@ -212,4 +413,90 @@ mod test_parse_string_quoted {
         );
     }
     
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test_split_pipeline {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        let val = split_pipeline("cat a | call wc >> b");
+        assert_eq!(val, vec!["cat a ", "|", " call wc ", ">>", " b"]);
+        let val = split_pipeline("call make &");
+        assert_eq!(val, vec!["call make ", "&", ""]);
+    }
+
+    #[test]
+    fn test_split_shell_call() {
+        let val = split_pipeline("call --shell ls | wc -l > n.txt");
+        assert_eq!(val, vec!["call --shell ls | wc -l > n.txt"]);
+        let val = split_pipeline("cat a | call -is sort | uniq &");
+        assert_eq!(val, vec!["cat a ", "|", " call -is sort | uniq ", "&", ""]);
+        let val = split_pipeline("call -s make && make install");
+        assert_eq!(val, vec!["call -s make && make install"]);
+        let val = split_pipeline("call ls -s | call wc");
+        assert_eq!(val, vec!["call ls -s ", "|", " call wc"]);
+    }
+
+    #[test]
+    fn test_split_quoted() {
+        let val = split_pipeline("call echo \"a | b\" '>' c");
+        assert_eq!(val, vec!["call echo \"a | b\" '>' c"]);
+    }
+}
+
+#[cfg(test)]
+mod test_expand_aliases {
+    use super::*;
+
+    fn make_aliases() -> HashMap<String, String> {
+        let mut aliases = HashMap::new();
+        aliases.insert("g".to_string(), "call grep".to_string());
+        aliases.insert("plot".to_string(), "call python plot.py --dpi 300 $1 -o $2".to_string());
+        aliases.insert("ls".to_string(), "ls -d".to_string());
+        aliases.insert("l".to_string(), "ls".to_string());
+        aliases
+    }
+
+    #[test]
+    fn test_expand() {
+        let aliases = make_aliases();
+        assert_eq!(expand_aliases("g foo \"a b\"", &aliases), "call grep foo \"a b\"");
+        assert_eq!(expand_aliases("plot a.csv b.png", &aliases), "call python plot.py --dpi 300 a.csv -o b.png");
+        assert_eq!(expand_aliases("plot a.csv", &aliases), "call python plot.py --dpi 300 a.csv -o ");
+        assert_eq!(expand_aliases("cat a | g x > g", &aliases), "cat a |call grep x> g");
+        assert_eq!(expand_aliases("cd g", &aliases), "cd g");
+    }
+
+    #[test]
+    fn test_expand_recursive() {
+        let aliases = make_aliases();
+        assert_eq!(expand_aliases("ls", &aliases), "ls -d");
+        assert_eq!(expand_aliases("l dir", &aliases), "ls -d dir");
+    }
+}
+
+#[cfg(test)]
+mod test_script_lines {
+    use super::*;
+
+    #[test]
+    fn test_comments() {
+        let text = "# setup\nmkdir a  # make a\n\ndesc a -d \"issue #1\"\ncall echo a#b\n";
+        assert_eq!(
+            script_lines(text),
+            vec![
+                (2, "mkdir a".to_string()),
+                (4, "desc a -d \"issue #1\"".to_string()),
+                (5, "call echo a#b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_continuation() {
+        let text = "cp a.txt \\\n  b.txt\nls \\\n";
+        assert_eq!(script_lines(text), vec![(1, "cp a.txt   b.txt".to_string()), (3, "ls".to_string())]);
+    }
+}
@@ -0,0 +1,262 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{get_vtree_path, is_virtual_file, _VIRTUAL_FILES};
+use super::tree::{TreeItem, core::unique_path};
+
+const _TRASH: &str = "trash";
+
+/// A removed tree in .vtree/trash.
+pub struct TrashEntry {
+    pub path: PathBuf,  // Directory of the entry, such as .vtree/trash/1700000000.
+    pub name: String,  // Name of the removed tree.
+    pub removed: u64,  // Time of the removal in seconds since the Unix epoch.
+}
+
+impl TrashEntry {
+    /// Path of the tree json file in the entry.
+    pub fn json_path(&self) -> PathBuf {
+        self.path.join(format!("{}.json", self.name))
+    }
+
+    /// Number of virtual files in the entry.
+    pub fn file_count(&self) -> usize {
+        std::fs::read_dir(self.path.join(_VIRTUAL_FILES))
+            .map(|entries| entries.count())
+            .unwrap_or(0)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Path of the trash directory, .vtree/trash.
+pub fn trash_path() -> std::io::Result<PathBuf> {
+    Ok(get_vtree_path(true)?.join(_TRASH))
+}
+
+/// Move the tree json file at `json` and the virtual files `files` into a new
+/// directory `trash`/<timestamp>. Returns the new directory.
+pub fn move_to_trash(trash: &Path, json: &Path, files: &[PathBuf]) -> std::io::Result<PathBuf> {
+    let dir = unique_path(trash.join(now().to_string()))?;
+    let vfiles = dir.join(_VIRTUAL_FILES);
+    std::fs::create_dir_all(&vfiles)?;
+    for file in files {
+        if let Some(filename) = file.file_name().filter(|_| file.exists()) {
+            std::fs::rename(file, vfiles.join(filename))?;
+        }
+    }
+    std::fs::rename(json, dir.join(json.file_name().unwrap()))?;
+    Ok(dir)
+}
+
+/// List the entries in the trash directory `trash`, from the oldest to the latest.
+pub fn entries(trash: &Path) -> std::io::Result<Vec<TrashEntry>> {
+    if !trash.exists() {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(trash)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        // the directory name is the timestamp, with a suffix such as "-0" if needed
+        let dirname = path.file_name().unwrap().to_string_lossy().to_string();
+        let removed = match dirname.split('-').next().and_then(|s| s.parse().ok()) {
+            Some(removed) => removed,
+            None => continue,
+        };
+        for file in std::fs::read_dir(&path)? {
+            let file = file?.path();
+            if file.extension().is_some_and(|ext| ext == "json") {
+                let name = file.file_stem().unwrap().to_string_lossy().to_string();
+                entries.push(TrashEntry { path: path.clone(), name, removed });
+            }
+        }
+    }
+    entries.sort_by(|a, b| (a.removed, &a.path).cmp(&(b.removed, &b.path)));
+    Ok(entries)
+}
+
+/// Move the tree json file and the virtual files in `entry` back to `json` and
+/// their original paths in `vfiles`, and delete the entry. Virtual files that were
+/// not moved to the trash, such as the ones shared with other trees, are left as
+/// they are.
+/// # Errors
+/// If any of the original paths already exists, nothing is restored.
+pub fn restore(entry: &TrashEntry, json: &Path, vfiles: &Path) -> std::io::Result<()> {
+    let tree = TreeItem::from_file(&entry.json_path())?;
+    let trashed_files = entry.path.join(_VIRTUAL_FILES);
+    let mut moves = Vec::new();
+    for item in tree.entities() {
+        let path = match &item.entity {
+            Some(path) if is_virtual_file(path, vfiles) => path,
+            _ => continue,
+        };
+        let trashed = trashed_files.join(path.file_name().unwrap());
+        if !trashed.is_file() || moves.iter().any(|(other, _)| other == &trashed) {
+            continue;
+        }
+        if path.exists() {
+            return Err(
+                std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{} already exists.", path.display()),
+                )
+            );
+        }
+        moves.push((trashed, path.clone()));
+    }
+    for (trashed, path) in moves {
+        std::fs::rename(trashed, path)?;
+    }
+    std::fs::rename(entry.json_path(), json)?;
+    std::fs::remove_dir_all(&entry.path)
+}
+
+/// Entries removed at least `older_than` ago, or all the entries if it is None.
+pub fn expired(trash: &Path, older_than: Option<Duration>) -> std::io::Result<Vec<TrashEntry>> {
+    let now = now();
+    let older_than = older_than.map(|d| d.as_secs()).unwrap_or(0);
+    Ok(entries(trash)?
+        .into_iter()
+        .filter(|entry| now.saturating_sub(entry.removed) >= older_than)
+        .collect())
+}
+
+/// Parse a duration such as "30d", "12h", "45m", "10s" or "2w". A number
+/// without a unit is a number of days.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => s.split_at(idx),
+        None => (s, "d"),
+    };
+    let scale = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    let number: u64 = number.parse().ok()?;
+    Some(Duration::from_secs(number * scale))
+}
+
+/// Describe how long ago `timestamp` was, such as "3d ago".
+pub fn format_age(timestamp: u64) -> String {
+    let age = now().saturating_sub(timestamp);
+    match age {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", age / 60),
+        3600..=86399 => format!("{}h ago", age / 3600),
+        _ => format!("{}d ago", age / 86400),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Create an empty directory under ./target for a test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::current_dir().unwrap().join("target").join(name);
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        std::fs::create_dir_all(dir.join(_VIRTUAL_FILES)).unwrap();
+        dir
+    }
+
+    /// Write a tree json file with a virtual file `file` at ~/file.
+    fn write_tree(json: &Path, file: &Path) {
+        let text = format!(
+            r#"{{"name": "t", "children": [{{"name": "file", "children": [], "entity": {:?}}}]}}"#,
+            file.to_str().unwrap(),
+        );
+        std::fs::write(json, text).unwrap();
+    }
+
+    #[test]
+    fn test_move_and_restore() {
+        let dir = test_dir("test-trash-restore");
+        let trash = dir.join("trash");
+        let json = dir.join("t.json");
+        let file = dir.join(_VIRTUAL_FILES).join("a.txt");
+        write_tree(&json, &file);
+        std::fs::write(&file, "a").unwrap();
+
+        let first = move_to_trash(&trash, &json, std::slice::from_ref(&file)).unwrap();
+        assert!(!json.exists() && !file.exists());
+        assert!(first.join(_VIRTUAL_FILES).join("a.txt").exists());
+
+        // a tree removed in the same second gets a "-0" suffix
+        write_tree(&json, &file);
+        let second = move_to_trash(&trash, &json, &[]).unwrap();
+        assert_eq!(second.file_name(), Some(format!("{}-0", first.file_name().unwrap().to_str().unwrap()).as_ref()));
+        let entries = entries(&trash).unwrap();
+        assert_eq!(entries.iter().map(|e| &e.path).collect::<Vec<_>>(), [&first, &second]);
+        assert!(entries.iter().all(|e| e.name == "t" && e.removed == entries[0].removed));
+        assert_eq!((entries[0].file_count(), entries[1].file_count()), (1, 0));
+
+        // an existing file is not overwritten, and nothing is restored
+        std::fs::write(&file, "new").unwrap();
+        assert!(restore(&entries[0], &json, &dir.join(_VIRTUAL_FILES)).is_err());
+        assert!(!json.exists() && first.exists());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "new");
+
+        std::fs::remove_file(&file).unwrap();
+        restore(&entries[0], &json, &dir.join(_VIRTUAL_FILES)).unwrap();
+        assert!(json.exists() && !first.exists());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "a");
+
+        // the file that was not moved to the trash is kept
+        std::fs::remove_file(&json).unwrap();
+        restore(&entries[1], &json, &dir.join(_VIRTUAL_FILES)).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "a");
+        assert!(super::entries(&trash).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_expired() {
+        let dir = test_dir("test-trash-expired");
+        let trash = dir.join("trash");
+        let old = now() - 3 * 86400;
+        for name in [old.to_string(), format!("{}-0", old), now().to_string(), "other".to_string()] {
+            std::fs::create_dir_all(trash.join(&name)).unwrap();
+            std::fs::write(trash.join(&name).join("t.json"), "{}").unwrap();
+        }
+        let names = |entries: Vec<TrashEntry>| -> Vec<String> {
+            entries.iter().map(|e| e.path.file_name().unwrap().to_str().unwrap().to_string()).collect()
+        };
+        assert_eq!(names(expired(&trash, None).unwrap()).len(), 3);
+        assert_eq!(
+            names(expired(&trash, parse_duration("2d")).unwrap()),
+            [old.to_string(), format!("{}-0", old)],
+        );
+        assert!(expired(&trash, parse_duration("4d")).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30d"), Some(Duration::from_secs(30 * 86400)));
+        assert_eq!(parse_duration("12h"), Some(Duration::from_secs(12 * 3600)));
+        assert_eq!(parse_duration("2w"), Some(Duration::from_secs(14 * 86400)));
+        assert_eq!(parse_duration("7"), Some(Duration::from_secs(7 * 86400)));
+        assert_eq!(parse_duration("0s"), Some(Duration::from_secs(0)));
+        assert_eq!(parse_duration("d"), None);
+        assert_eq!(parse_duration("3 days"), None);
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(now()), "just now");
+        assert_eq!(format_age(now() - 150), "2m ago");
+        assert_eq!(format_age(now() - 3 * 86400), "3d ago");
+    }
+}
@@ -1,6 +1,7 @@
 use super::{tree_item::TreeItem, error::TreeError};
-use std::{path::PathBuf, process::Command};
+use std::{collections::HashMap, path::PathBuf, process::Command};
 use std::io::Write;
+use unicode_width::UnicodeWidthStr;
 use super::error::Result;
 
 
@@ -53,6 +54,14 @@ impl PathVector {
     }
 }
 
+/// Options of an external command called from the virtual terminal.
+#[derive(Default)]
+pub struct CallOptions {
+    pub shell: Option<Vec<String>>,  // Shell and its arguments, such as `sh -c`.
+    pub no_resolve: bool,  // If true, arguments are not guessed to be virtual paths.
+    pub variables: HashMap<String, String>,  // Variables expanded in the arguments and exported.
+}
+
 /// A struct with a tree and the current position.
 /// TreeModel is used to implement moving forward/backward in a tree.
 pub struct TreeModel {
@@ -77,13 +86,15 @@ impl TreeModel {
         TreeModel::new(item)
     }
 
-    /// Write the tree to a json file at `path`.
+    /// Write the tree to a json file at `path`. The tree is written to a temporary
+    /// file first and then renamed, so that the file is never left half-written.
     pub fn to_file(&self, path: &std::path::Path) -> std::io::Result<()> {
         let serialized = serde_json::to_string_pretty(&self.root).unwrap();
-        // write
-        let mut file = std::fs::File::create(path)?;
-
-        file.write_all(serialized.as_bytes())
+        let tmp = path.with_extension("json.tmp");
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(serialized.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)
     }
 
     /// Get the current tree item.
@@ -285,9 +296,8 @@ impl TreeModel {
         }
     }
 
-    /// Open file at `path` using default application.
-    pub fn open_file(&self, path: &String) -> Result<()> {
-        use open::that;
+    /// Open file at `path` using default application, or `opener` if given.
+    pub fn open_file(&self, path: &String, opener: Option<&str>) -> Result<()> {
         let pathvec = self.resolve_virtual_path(path);
         let item = self.item_at(&pathvec)?;
         let entity_path = match item.entity.as_ref() {
@@ -299,7 +309,11 @@ impl TreeModel {
             Err(err) => return Err(TreeError::new(format!("{}", err))),
         };
 
-        match that(path) {
+        let result = match opener {
+            Some(opener) => open::with(path, opener),
+            None => open::that(path),
+        };
+        match result {
             Ok(_) => Ok(()),
             Err(err) => Err(
                 TreeError::new(format!("Error opening file: {}", err))
@@ -320,6 +334,23 @@ impl TreeModel {
             None => Err(TreeError::new(format!("No entity found"))),
         }
     }
+
+    /// Get the absolute paths of all the entities at or under the item at `path`.
+    pub fn entity_abspaths(&self, path: &String) -> Result<Vec<PathBuf>> {
+        let item = self.get_item(path)?;
+        let entities = match item.entity_path() {
+            Some(rpath) => vec![rpath],
+            None => item.entities().iter().filter_map(|item| item.entity_path()).collect(),
+        };
+        entities
+            .into_iter()
+            .map(|rpath| {
+                resolve_path(rpath)
+                    .map_err(|_| TreeError::new(format!("Error resolving {}", rpath)))
+            })
+            .collect()
+    }
+
     pub fn make_directory(&mut self, path: &String) -> Result<()> {
         let mut pathvec = self.resolve_virtual_path(path);
         let file_name = match pathvec.pop() {
@@ -334,6 +365,13 @@ impl TreeModel {
         Ok(())
     }
 
+    /// Set the description of the item at `path`.
+    pub fn set_description(&mut self, path: &String, desc: Option<String>) -> Result<()> {
+        let item = self.get_item_mut(path)?;
+        item.desc = desc;
+        Ok(())
+    }
+
     pub fn remove_child(&mut self, path: &String) -> Result<()> {
         let mut pathvec = self.resolve_virtual_path(path);
         let file_name = match pathvec.pop() {
@@ -345,6 +383,25 @@ impl TreeModel {
         item.remove_child(&file_name)
     }
 
+    /// Return the virtual paths that complete `word`. Virtual directories end with "/".
+    pub fn complete_path(&self, word: &str) -> Vec<String> {
+        let (dir, prefix) = match word.rfind('/') {
+            Some(idx) => word.split_at(idx + 1),
+            None => ("", word),
+        };
+        let item = match self.get_item(&format!("{}.", dir)) {
+            Ok(item) => item,
+            Err(_) => return Vec::new(),
+        };
+        item.iter_children()
+            .filter(|child| child.name.starts_with(prefix))
+            .map(|child| {
+                let suffix = if child.entity.is_none() { "/" } else { "" };
+                format!("{}{}{}", dir, child.name, suffix)
+            })
+            .collect()
+    }
+
     /// Return the text for "ls" command.
     pub fn ls_simple(&self, path: Option<String>) -> Result<String> {
         let path = match path {
@@ -373,16 +430,12 @@ impl TreeModel {
             let desc = child.desc.as_ref().unwrap_or(&default_desc);
             desc_vec.push(desc.clone());
         }
-        // find longest name to align descriptions
-        let mut max_len = 0;
-        for name in &name_vec {
-            if name.len() > max_len {
-                max_len = name.len();
-            }
-        }
+        // find the widest name to align descriptions
+        let max_width = name_vec.iter().map(|name| name.width()).max().unwrap_or(0);
         let mut pair_vec: Vec<String> = Vec::new();
         for (name, desc) in name_vec.iter().zip(desc_vec.iter()) {
-            pair_vec.push(format!("{:>width$} {}", name, desc, width=max_len));
+            let pad = " ".repeat(max_width - name.width());
+            pair_vec.push(format!("{}{} {}", pad, name, desc));
         }
         Ok(pair_vec.join("\n"))
     }
@@ -429,32 +482,7 @@ impl TreeModel {
         }
         let filename = pathvec.pop().unwrap();
         // find unique file name
-        let mut path = candidate.clone();
-        let vpath_copy = path.clone();
-        let stem = vpath_copy.file_stem().unwrap().to_str().unwrap();
-        let ext = match vpath_copy.extension() {
-            Some(ext) => ".".to_string() + ext.to_str().unwrap(),
-            None => "".to_string(),
-        };
-        let mut count = 0;
-        // search for unique file name
-        let vpath = loop {
-            if !path.exists() {
-                break path;
-            }
-            let filename = format!("{}-{}{}", stem, count, ext);
-            path = match path.parent() {
-                Some(parent) => parent.join(filename),
-                None => {
-                    return Err(
-                        TreeError::new(
-                            format!("{} already exists.", path.to_str().unwrap())
-                        )
-                    )
-                }
-            };
-            count += 1;
-        };
+        let vpath = unique_path(candidate)?;
 
         // create a hidden file
         match std::fs::File::create(&vpath) {
@@ -471,57 +499,174 @@ impl TreeModel {
         item.add_new_child(&filename, vpath)
     }
 
-    /// Call external command from the virtual terminal.
-    /// let vec = vec!["ls".to_string(), "-l".to_string()];
-    /// self.call_command(&vec)
-    pub fn call_command(&self, inputs: &Vec<String>) -> Result<()> {
-        let mut output: Vec<String> = Vec::new();
-        for arg in inputs {
-            let pathvec = self.resolve_virtual_path(arg);
-            let arg = match self.item_at(&pathvec) {
-                Ok(item) => {
-                    match item.entity.as_ref() {
-                        Some(entity) => {
-                            let entity_path = entity.to_str().unwrap();
-                            match resolve_path(entity_path) {
-                                Ok(path) => path.to_str().unwrap().to_string(),
-                                Err(_) => arg.clone()
-                            }
-                        }
-                        None => arg.clone(),
-                    }
-                }
-                Err(_) => {
-                    arg.clone()
-                }
-            };
+    /// Write `text` to the file at `path`, or append it if `append` is true. If
+    /// the file does not exist, it is created at `candidate` as `create_new_file`
+    /// does.
+    pub fn write_file(
+        &mut self, path: &String, text: &str, append: bool, candidate: PathBuf
+    ) -> Result<()> {
+        let pathvec = self.resolve_virtual_path(path);
+        if !self.check_path_exists(pathvec.iter()) {
+            self.create_new_file(path, candidate)?;
+        }
+        if !self.item_at(&pathvec)?.is_file() {
+            return Err(TreeError::new(format!("{} is not a file.", path)));
+        }
+        let entity = self.entity_abspath(path)?;
+        std::fs::OpenOptions::new()
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(&entity)
+            .and_then(|mut file| file.write_all(text.as_bytes()))
+            .map_err(|err| TreeError::new(format!("{}: {}", entity.display(), err)))
+    }
 
-            let abspath = resolve_path(&arg);
-            match abspath {
-                Ok(abspath) => {
-                    output.push(abspath.to_str().unwrap().to_string());
-                }
-                Err(_) => {
-                    output.push(arg.to_string());
+    /// Get the absolute virtual paths, such as "~/data/a.csv", of the files
+    /// matching a glob pattern. `*` and `?` match any characters in a name and `**`
+    /// matches any number of directories.
+    pub fn glob(&self, pattern: &str) -> Result<Vec<String>> {
+        let mut parts: Vec<&str> = pattern.split(['/', '\\']).collect();
+        let mut base = if parts.first() == Some(&"~") { Vec::new() } else { self.path.path.clone() };
+        while let Some(&part) = parts.first() {
+            match part {
+                ".." => { base.pop(); }
+                "" | "." | "~" => {}
+                _ => break,
+            }
+            parts.remove(0);
+        }
+        let mut paths = Vec::new();
+        glob_items(self.item_at(&base)?, &mut base, &parts, &mut paths);
+        let mut found = std::collections::HashSet::new();
+        paths.retain(|path| found.insert(path.clone()));
+        Ok(paths)
+    }
+
+    /// Get the absolute path of the entity of the item at `path`.
+    fn resolve_entity(&self, path: &str) -> Result<String> {
+        let pathvec = self.resolve_virtual_path(&path.to_string());
+        let item = self.item_at(&pathvec)?;
+        let entity = item
+            .entity_path()
+            .ok_or_else(|| TreeError::new(format!("{} does not have an entity.", path)))?;
+        resolve_path(entity)
+            .ok()
+            .and_then(|path| path.to_str().map(|s| s.to_string()))
+            .ok_or_else(|| TreeError::new(format!("Error resolving {}", entity)))
+    }
+
+    /// Resolve an argument of an external command. If the argument is a virtual
+    /// path of an item with an entity, the path of the entity is returned.
+    fn resolve_argument(&self, arg: &str) -> Option<String> {
+        self.resolve_entity(arg).ok()
+    }
+
+    /// Replace the placeholders such as `{data/a.csv}` in `arg` with the entity
+    /// paths, converted by `convert`. `{{` and `}}` are the literal braces, and
+    /// braces around a text that is not a virtual path with an entity, such as
+    /// `{print}` of awk, are kept as they are. Also returns true if any
+    /// placeholder was found.
+    fn expand_placeholders(
+        &self, arg: &str, convert: impl Fn(String) -> String
+    ) -> (String, bool) {
+        let mut out = String::new();
+        let mut found = false;
+        let mut rest = arg;
+        while let Some(c) = rest.chars().next() {
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                out.push(c);
+                rest = &rest[2..];
+                continue;
+            }
+            if c == '{' {
+                if let Some(end) = rest.find('}') {
+                    let name = &rest[1..end];
+                    let is_name = !name.is_empty()
+                        && !name.contains(char::is_whitespace)
+                        && !name.contains('{');
+                    if let Some(path) = self.resolve_entity(name).ok().filter(|_| is_name) {
+                        out.push_str(&convert(path));
+                        found = true;
+                        rest = &rest[end + 1..];
+                        continue;
+                    }
                 }
             }
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
         }
+        (out, found)
+    }
 
-        // Run command.
-        // NOTE: `spawn` is not appropriate for such as `vim`.
-        let cmd = if cfg!(target_os = "windows") {
-            let mut args = vec!("/C".to_string());
-            args.extend(output);
-            Command::new("cmd").args(&args).status()
+    /// Expand an argument of an external command in the following order.
+    /// 1. Unless the command is run in the shell, `$NAME` and `${NAME}` are
+    ///    replaced by the variables. The shell expands them by itself because they
+    ///    are exported.
+    /// 2. Placeholders such as `{data/a.csv}` are replaced by the entity paths.
+    /// 3. Unless `no_resolve` is true, an argument without placeholders that is a
+    ///    virtual path of an item with an entity is replaced by the entity path.
+    ///
+    /// Substituted paths are quoted if the command is run in the shell.
+    fn expand_argument(&self, arg: &str, options: &CallOptions) -> Result<String> {
+        let quote = |path: String| {
+            if options.shell.is_some() { shell_quote(&path) } else { path }
+        };
+        let arg = match options.shell {
+            Some(_) => arg.to_string(),
+            None => expand_variables(arg, &options.variables),
+        };
+        let (arg, found) = self.expand_placeholders(&arg, quote);
+        if found || options.no_resolve {
+            return Ok(arg);
         }
-        else {
-            let mut args = vec!("-C".to_string());
-            args.extend(output);
-            Command::new("sh").args(&args).status()
+        Ok(self.resolve_argument(&arg).map(quote).unwrap_or(arg))
+    }
+
+    /// Build an external command called from the virtual terminal. The arguments
+    /// are expanded by `expand_argument` and the variables are exported to the
+    /// command.
+    ///
+    /// If `options.shell` is None, the first argument is run as a program with the
+    /// rest as its arguments. Otherwise, the arguments are joined into a command
+    /// line that is passed to the shell, such as `sh -c`. Substituted paths are
+    /// quoted but the other arguments are not, so that shell syntax such as pipes
+    /// works.
+    pub fn command(&self, inputs: &[String], options: &CallOptions) -> Result<Command> {
+        let args = inputs
+            .iter()
+            .map(|arg| self.expand_argument(arg, options))
+            .collect::<Result<Vec<String>>>()?;
+        if args.is_empty() {
+            return Err(TreeError::new("No command given.".to_string()));
+        }
+        let mut cmd = match &options.shell {
+            None => {
+                let mut cmd = Command::new(&args[0]);
+                cmd.args(&args[1..]);
+                cmd
+            }
+            Some(shell) => {
+                let (program, shell_args) = shell
+                    .split_first()
+                    .ok_or_else(|| TreeError::new("Shell is not specified.".to_string()))?;
+                let mut cmd = Command::new(program);
+                cmd.args(shell_args).arg(args.join(" "));
+                cmd
+            }
         };
-        
-        match cmd {
-            Ok(_) => Ok(()),
+        cmd.envs(&options.variables);
+        Ok(cmd)
+    }
+
+    /// Call external command from the virtual terminal with the inherited stdio,
+    /// and return the exit code.
+    /// let vec = vec!["ls".to_string(), "-l".to_string()];
+    /// self.call_command(&vec, &CallOptions::default())
+    pub fn call_command(&self, inputs: &[String], options: &CallOptions) -> Result<i32> {
+        // NOTE: `spawn` is not appropriate for such as `vim`.
+        match self.command(inputs, options)?.status() {
+            Ok(status) => Ok(status.code().unwrap_or(1)),
             Err(err) => Err(
                 TreeError::new(
                     format!("Error calling command: {}", err)
@@ -531,23 +676,146 @@ impl TreeModel {
     }
 }
 
+/// Find a path that does not exist yet by adding a number to the file name of
+/// `candidate`, such as "a-0.txt".
+pub fn unique_path(candidate: PathBuf) -> Result<PathBuf> {
+    let mut path = candidate.clone();
+    let stem = candidate.file_stem().unwrap().to_str().unwrap();
+    let ext = match candidate.extension() {
+        Some(ext) => ".".to_string() + ext.to_str().unwrap(),
+        None => "".to_string(),
+    };
+    let mut count = 0;
+    // search for unique file name
+    loop {
+        if !path.exists() {
+            return Ok(path);
+        }
+        let filename = format!("{}-{}{}", stem, count, ext);
+        path = match path.parent() {
+            Some(parent) => parent.join(filename),
+            None => {
+                return Err(
+                    TreeError::new(
+                        format!("{} already exists.", path.to_str().unwrap())
+                    )
+                )
+            }
+        };
+        count += 1;
+    }
+}
+
 /// Resolve input path string (must exist) and return a PathBuf with an absolute
 /// path. Input string can be a relative path in the virtual directory or an 
 /// existing absolute path.
 fn resolve_path(path: &str) -> std::io::Result<PathBuf> {
-    if path.starts_with(".") || path.starts_with("/") {
-        let curdir = std::env::current_dir()?;
-        let path = path
-            .strip_prefix(".")
-            .unwrap_or(path)
-            .strip_prefix("/")
-            .unwrap_or(path);
-        let joined = std::path::Path::new(&curdir).join(path);
-        Ok(joined)
-    }
-    else {
-        let joined = std::path::Path::new(&path).to_path_buf();
-        Ok(joined)
+    let path = std::path::Path::new(path);
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+    // paths such as "./data/a.csv" and "data/a.csv" are relative to the current directory
+    let path = path.strip_prefix(".").unwrap_or(path);
+    Ok(std::env::current_dir()?.join(path))
+}
+
+/// Collect the virtual paths of the files under `item` at `path` matching the
+/// glob pattern split into `parts`.
+fn glob_items(item: &TreeItem, path: &mut Vec<String>, parts: &[&str], out: &mut Vec<String>) {
+    match parts.split_first() {
+        None => {
+            if item.is_file() {
+                out.push(format!("~/{}", path.join("/")));
+            }
+        }
+        Some((&"**", rest)) => {
+            glob_items(item, path, rest, out);
+            for child in item.iter_children() {
+                path.push(child.name.clone());
+                glob_items(child, path, parts, out);
+                path.pop();
+            }
+        }
+        Some((part, rest)) => {
+            for child in item.iter_children().filter(|child| wildcard_match(part, &child.name)) {
+                path.push(child.name.clone());
+                glob_items(child, path, rest, out);
+                path.pop();
+            }
+        }
+    }
+}
+
+/// Check if `name` matches a pattern in which `*` matches any characters and `?`
+/// matches a single character.
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // matched[j] is true if the pattern so far matches the first j characters
+    let mut matched = vec![false; name.len() + 1];
+    matched[0] = true;
+    for p in pattern {
+        let mut next = vec![false; name.len() + 1];
+        for j in 0..=name.len() {
+            next[j] = match p {
+                '*' => matched[j] || (j > 0 && next[j - 1]),
+                '?' => j > 0 && matched[j - 1],
+                c => j > 0 && matched[j - 1] && name[j - 1] == c,
+            };
+        }
+        matched = next;
+    }
+    matched[name.len()]
+}
+
+/// Replace `$NAME` and `${NAME}` in `s` with the values of the variables.
+/// Unknown variables are kept as they are.
+pub fn expand_variables(s: &str, variables: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(idx) = rest.find('$') {
+        out.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        let (name, len) = match rest[1..].strip_prefix('{') {
+            Some(tail) => match tail.find('}') {
+                Some(end) => (&tail[..end], end + 3),
+                None => ("", 0),
+            },
+            None => {
+                let end = rest[1..]
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len() - 1);
+                (&rest[1..end + 1], end + 1)
+            }
+        };
+        match variables.get(name) {
+            Some(value) if !name.is_empty() => {
+                out.push_str(value);
+                rest = &rest[len..];
+            }
+            _ => {
+                out.push('$');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Quote a string so that the shell treats it as a single word.
+pub fn shell_quote(s: &str) -> String {
+    if cfg!(target_os = "windows") {
+        if s.is_empty() || s.contains([' ', '\t', '&', '|', '<', '>', '^', '(', ')']) {
+            return format!("\"{}\"", s.replace('"', "\"\""));
+        }
+        return s.to_string();
+    }
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-+=@%:,./".contains(c);
+    if !s.is_empty() && s.chars().all(is_safe) {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
     }
 }
 
@@ -667,6 +935,16 @@ mod test_tree_model {
     }
     
 
+    #[test]
+    fn test_ls_detailed_aligned_by_width() {
+        let mut tree = TreeModel::from_string(JSON_0);
+        tree.make_directory(&"日本語".to_string()).unwrap();
+        assert_eq!(
+            tree.ls_detailed(None).unwrap(),
+            " dir-A <no description>\n dir-B <no description>\n日本語 <no description>"
+        );
+    }
+
     #[test]
     fn test_mkdir() {
         let mut tree = TreeModel::from_string(JSON_0);
@@ -686,6 +964,16 @@ mod test_tree_model {
         assert_eq!(tree.ls_simple(Some("dir-A".to_string())).unwrap(), "sub-dir");
     }
 
+    #[test]
+    fn test_set_description() {
+        let mut tree = TreeModel::from_string(JSON_0);
+        tree.set_description(&"dir-A/item.txt".to_string(), Some("new".to_string())).unwrap();
+        assert_eq!(tree.get_item(&"dir-A/item.txt".to_string()).unwrap().desc, Some("new".to_string()));
+        tree.move_forward("dir-B".to_string()).unwrap();
+        tree.set_description(&".".to_string(), Some("dir".to_string())).unwrap();
+        assert_eq!(tree.current_item().unwrap().desc, Some("dir".to_string()));
+    }
+
     #[test]
     fn test_move_dir() {
         let mut tree = TreeModel::from_string(JSON_0);
@@ -747,4 +1035,154 @@ mod test_tree_model {
         }
         assert_eq!(tree.ls_simple(None).unwrap(), "item.txt");
     }
+
+    #[test]
+    fn test_command_without_shell() {
+        let tree = TreeModel::from_string(JSON_0);
+        let inputs = vec!["cat".to_string(), "dir-A/item.txt".to_string(), "a b".to_string()];
+        let cmd = tree.command(&inputs, &CallOptions::default()).unwrap();
+        assert_eq!(cmd.get_program(), "cat");
+        let args: Vec<_> = cmd.get_args().map(|arg| arg.to_str().unwrap().to_string()).collect();
+        assert!(args[0].ends_with("src/main.rs"));
+        assert_eq!(args[1], "a b");
+        assert!(tree.command(&[], &CallOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_command_expansion() {
+        let tree = TreeModel::from_string(JSON_0);
+        let mut options = CallOptions { no_resolve: true, ..Default::default() };
+        options.variables.insert("OUT".to_string(), "out".to_string());
+        let inputs: Vec<String> = ["echo", "--input={dir-A/item.txt}", "dir-A/item.txt", "${OUT}.csv", "$X", "{}", "{{a}}"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let cmd = tree.command(&inputs, &options).unwrap();
+        let args: Vec<_> = cmd.get_args().map(|arg| arg.to_str().unwrap().to_string()).collect();
+        assert!(args[0].starts_with("--input=/") && args[0].ends_with("src/main.rs"));
+        assert_eq!(&args[1..], ["dir-A/item.txt", "out.csv", "$X", "{}", "{a}"]);
+        assert!(cmd.get_envs().any(|(key, value)| key == "OUT" && value == Some("out".as_ref())));
+
+        // braces that are not placeholders are kept, such as the ones of awk and jq
+        let inputs: Vec<String> = ["awk", "{print}", "{\"a\":1}", ".x|{y}", "{dir-B}", "{dir-A/item.txt"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let cmd = tree.command(&inputs, &options).unwrap();
+        let args: Vec<_> = cmd.get_args().map(|arg| arg.to_str().unwrap().to_string()).collect();
+        assert_eq!(args, ["{print}", "{\"a\":1}", ".x|{y}", "{dir-B}", "{dir-A/item.txt"]);
+    }
+
+    #[test]
+    fn test_glob() {
+        let mut tree = TreeModel::from_string(JSON_0);
+        let all = vec!["~/dir-A/item.txt".to_string(), "~/dir-A/sub-dir/item2.txt".to_string()];
+        assert_eq!(tree.glob("**/*.txt").unwrap(), all);
+        assert_eq!(tree.glob("dir-?/*").unwrap(), all[..1]);
+        assert_eq!(tree.glob("*/sub-dir/item?.txt").unwrap(), all[1..]);
+        assert!(tree.glob("dir-B/*").unwrap().is_empty());
+        tree.move_by_string(&"dir-A/sub-dir".to_string()).unwrap();
+        assert_eq!(tree.glob("../*.txt").unwrap(), all[..1]);
+        assert_eq!(tree.glob("~/**/item2.*").unwrap(), all[1..]);
+    }
+
+    #[test]
+    fn test_entity_abspaths() {
+        let tree = TreeModel::from_string(JSON_0);
+        let main = std::env::current_dir().unwrap().join("src/main.rs");
+        assert_eq!(tree.entity_abspaths(&"~".to_string()).unwrap(), vec![main.clone(), main.clone()]);
+        assert_eq!(tree.entity_abspaths(&"dir-A/sub-dir".to_string()).unwrap(), vec![main.clone()]);
+        assert_eq!(tree.entity_abspaths(&"dir-A/item.txt".to_string()).unwrap(), vec![main]);
+        assert!(tree.entity_abspaths(&"dir-B".to_string()).unwrap().is_empty());
+        assert!(tree.entity_abspaths(&"dir-C".to_string()).is_err());
+
+        // absolute entity paths are kept as they are
+        let absolute = std::env::temp_dir().join("vt").join("a.csv");
+        let json = format!(
+            r#"{{"name": "t", "children": [{{"name": "a.csv", "children": [], "entity": {:?}}}]}}"#,
+            absolute.to_str().unwrap(),
+        );
+        let tree = TreeModel::from_string(&json);
+        assert_eq!(tree.entity_abspaths(&"~".to_string()).unwrap(), vec![absolute.clone()]);
+        assert_eq!(tree.entity_abspath(&"a.csv".to_string()).unwrap(), absolute);
+    }
+
+    #[test]
+    fn test_complete_path() {
+        let tree = TreeModel::from_string(JSON_0);
+        assert_eq!(tree.complete_path(""), vec!["dir-A/", "dir-B/"]);
+        assert_eq!(tree.complete_path("dir-A/"), vec!["dir-A/item.txt", "dir-A/sub-dir/"]);
+        assert_eq!(tree.complete_path("~/dir-A/s"), vec!["~/dir-A/sub-dir/"]);
+        assert_eq!(tree.complete_path("dir-A/sub-dir/i"), vec!["dir-A/sub-dir/item2.txt"]);
+        assert!(tree.complete_path("dir-C/").is_empty());
+        assert!(tree.complete_path("dir-A/x").is_empty());
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.csv", "a.csv"));
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("a?c*", "abcde"));
+        assert!(!wildcard_match("*.csv", "a.txt"));
+        assert!(!wildcard_match("a?", "a"));
+    }
+
+    #[test]
+    fn test_expand_variables() {
+        let mut variables = HashMap::new();
+        variables.insert("A".to_string(), "x".to_string());
+        variables.insert("A_1".to_string(), "y".to_string());
+        assert_eq!(expand_variables("$A/${A}b/$A_1/$B/$/${A", &variables), "x/xb/y/$B/$/${A");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_with_shell() {
+        let tree = TreeModel::from_string(JSON_0);
+        let options = CallOptions {
+            shell: Some(vec!["sh".to_string(), "-c".to_string()]),
+            ..Default::default()
+        };
+        let inputs = vec!["wc".to_string(), "dir-A/item.txt".to_string(), "|".to_string(), "cat".to_string()];
+        let cmd = tree.command(&inputs, &options).unwrap();
+        assert_eq!(cmd.get_program(), "sh");
+        let args: Vec<_> = cmd.get_args().map(|arg| arg.to_str().unwrap().to_string()).collect();
+        let path = tree.entity_abspath(&"dir-A/item.txt".to_string()).unwrap();
+        assert_eq!(args, vec!["-c".to_string(), format!("wc {} | cat", shell_quote(path.to_str().unwrap()))]);
+    }
+
+    #[test]
+    fn test_to_file() {
+        let tree = TreeModel::from_string(JSON_0);
+        let path = PathBuf::from("./target/test-to-file.json");
+        tree.to_file(&path).unwrap();
+        assert!(!path.with_extension("json.tmp").exists());
+        let loaded = TreeModel::from_file(&path).unwrap();
+        assert_eq!(loaded.ls_simple(None).unwrap(), "dir-A dir-B");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_write_file() {
+        let mut tree = TreeModel::from_string(JSON_0);
+        let candidate = PathBuf::from("./target/test-write-file.txt");
+        let _ = std::fs::remove_file(&candidate);
+        let name = "dir-B/out.txt".to_string();
+        tree.write_file(&name, "a\n", false, candidate.clone()).unwrap();
+        tree.write_file(&name, "b\n", true, candidate.clone()).unwrap();
+        assert_eq!(tree.read_file(&name).unwrap(), "a\nb\n");
+        tree.write_file(&name, "c\n", false, candidate.clone()).unwrap();
+        assert_eq!(tree.read_file(&name).unwrap(), "c\n");
+        assert!(tree.write_file(&"dir-A".to_string(), "", false, candidate.clone()).is_err());
+        std::fs::remove_file(&candidate).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/a/b.txt"), "/a/b.txt");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
 }
@@ -21,4 +21,9 @@ impl fmt::Display for TreeError {
 
 impl std::error::Error for TreeError {
     
-}
\ No newline at end of file
+}
+impl From<TreeError> for std::io::Error {
+    fn from(err: TreeError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, err.msg)
+    }
+}
@@ -3,4 +3,4 @@ pub mod core;
 pub mod tree_item;
 
 pub use self::tree_item::TreeItem;
-pub use self::core::TreeModel;
+pub use self::core::{TreeModel, CallOptions};
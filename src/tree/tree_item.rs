@@ -266,61 +266,42 @@ impl TreeItem {
         }
     }
 
-    fn _fmt_with_indent(&self, f: &mut std::fmt::Formatter, level: usize) -> std::fmt::Result{
-        let blk = "│  ".repeat(level - 1);
-        write!(f, "  {}├─ {}\n", blk, self.name)?;
-        let nch = self.children.len();
-        if nch == 0 {
-            return Ok(())
-        }
-        let mut iter = self.children.iter();
-        for _ in 0..nch-1 {
-            let child = iter.next().unwrap();
-            child._fmt_with_indent(f, level + 1)?;
-        }
-        let child = iter.next().unwrap();
-        child._fmt_with_indent_last(f, level + 1)?;
-        Ok(())
+    /// Return the lines of the tree diagram. Each line is paired with the path of
+    /// the item relative to this item.
+    pub fn tree_lines(&self) -> Vec<(String, Vec<String>)> {
+        let mut lines = vec![(self.name.clone(), Vec::new())];
+        self.collect_tree_lines(1, &mut Vec::new(), &mut lines);
+        lines
     }
 
-    fn _fmt_with_indent_last(&self, f: &mut std::fmt::Formatter, level: usize) -> std::fmt::Result{
+    fn collect_tree_lines(
+        &self, level: usize, path: &mut Vec<String>, lines: &mut Vec<(String, Vec<String>)>
+    ) {
         let blk = "│  ".repeat(level - 1);
-        write!(f, "  {}└─ {}\n", blk, self.name)?;
         let nch = self.children.len();
-        if nch == 0 {
-            return Ok(())
-        }
-        let mut iter = self.children.iter();
-        for _ in 0..nch-1 {
-            let child = iter.next().unwrap();
-            child._fmt_with_indent(f, level + 1)?;
+        for (idx, child) in self.children.iter().enumerate() {
+            let branch = if idx == nch - 1 { "└─" } else { "├─" };
+            path.push(child.name.clone());
+            lines.push((format!("  {}{} {}", blk, branch, child.name), path.clone()));
+            child.collect_tree_lines(level + 1, path, lines);
+            path.pop();
         }
-        let child = iter.next().unwrap();
-        child._fmt_with_indent_last(f, level + 1)?;
-        Ok(())
     }
-
 }
 
 // Implement functions that format the tree item.
 impl std::fmt::Display for TreeItem {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}\n", self.name)?;
-        let nch = self.children.len();
-        let mut iter = self.children.iter();
-        for _ in 0..nch-1 {
-            let child = iter.next().unwrap();
-            child._fmt_with_indent(f, 1)?;
+        for (line, _) in self.tree_lines() {
+            writeln!(f, "{}", line)?;
         }
-        let child = iter.next().unwrap();
-        child._fmt_with_indent_last(f, 1)?;
         Ok(())
     }
 }
 
 const _INVALID: &str = "\\/#|\"*?<>:";
 
-fn is_valid_item_name(name: &String) -> bool {
+pub fn is_valid_item_name(name: &str) -> bool {
     for c in name.chars() {
         if _INVALID.contains(c) {
             return false
@@ -349,10 +330,25 @@ mod test {
 
     #[test]
     fn test_is_valid() {
-        assert!(is_valid_item_name(&"foo".to_string()));
-        assert!(is_valid_item_name(&"123.txt".to_string()));
-        assert!(!is_valid_item_name(&"2/3".to_string()));
-        assert!(!is_valid_item_name(&"3#.json".to_string()));
+        assert!(is_valid_item_name("foo"));
+        assert!(is_valid_item_name("123.txt"));
+        assert!(!is_valid_item_name("2/3"));
+        assert!(!is_valid_item_name("3#.json"));
+    }
+
+    #[test]
+    fn test_tree_lines() {
+        let mut item = TreeItem::new("root".to_string());
+        item.make_directory(&"a".to_string()).unwrap();
+        item.make_directory(&"b".to_string()).unwrap();
+        item.get_child_dir_mut(&"a".to_string()).unwrap().make_directory(&"c".to_string()).unwrap();
+        let lines = item.tree_lines();
+        assert_eq!(
+            lines.iter().map(|(line, _)| line.as_str()).collect::<Vec<_>>(),
+            vec!["root", "  ├─ a", "  │  └─ c", "  └─ b"]
+        );
+        assert_eq!(lines[2].1, vec!["a".to_string(), "c".to_string()]);
+        assert_eq!(format!("{}", TreeItem::new("empty".to_string())), "empty\n");
     }
 
     #[test]
@@ -1,18 +1,45 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use unicode_segmentation::UnicodeSegmentation;
 use tui::{
-    style::{Color, Style},
+    layout::Rect,
+    style::Style,
     text::Text,
 };
 use super::{
-    rich::{RichText, RichLine},
+    rich::{RichText, RichLine, grapheme_count, grapheme_offset, display_width},
     history::History,
+    highlight::{Highlighter, plain_lines},
+    preview::Preview,
+    browser::Browser,
+    scrollback::{Search, LineSelection},
+    keymap::{Keymap, EditMode},
+    clipboard::KillRing,
+    theme::{Palette, no_color_requested},
+    prompt::PromptState,
+    process::{OutputLine, sanitize_line},
+    jobs::Jobs,
     super::{
+        config::Config,
+        get_relative_vtree_path,
         terminal::parse_string_raw,
-        tree,
+        tree::{self, CallOptions, error::TreeError},
     },
 };
 
 const _VIRTUAL_FILES: &str = "virtual-files";
 
+/// Candidate path of the virtual file created for the virtual path `name`.
+pub fn virtual_file_candidate(name: &str) -> std::io::Result<PathBuf> {
+    let filename = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    Ok(get_relative_vtree_path(true)?.join(_VIRTUAL_FILES).join(filename))
+}
+
+// Variables given to `call` by vtree, which cannot be set by `set`.
+const _BUILTIN_VARIABLES: [&str; 3] = ["VTREE_PWD", "VTREE_TREE", "VTREE_ROOT"];
+
+/// Cursor and selection in the input buffer. Positions are indices of grapheme
+/// clusters.
 pub struct Cursor {
     start: usize,
     pos: usize,
@@ -116,19 +143,159 @@ pub struct App {
     pub history: History<String>,
     pub tab_completion: TabCompleter,
     pub scroll_pos: usize,
+    pub highlighter: Highlighter,
+    pub preview: Preview,
+    pub browser: Browser,
+    pub text_area: Rect,  // The area where the lines are rendered.
+    pub search: Option<Search>,
+    pub line_selection: Option<LineSelection>,
+    pub keymap: Keymap,
+    pub kill_ring: KillRing,
+    pub last_yank: Option<(usize, usize)>,  // Start and end of the text just yanked.
+    pub palette: Palette,
+    pub config: Config,
+    pub last_status: i32,  // Exit status of the last command.
+    pub running: Option<String>,  // Name of the command running in the foreground.
+    pub capture: Option<String>,  // Output of the command, if it is piped or redirected.
+    pub variables: HashMap<String, String>,  // Session variables defined by `set`.
+    pub sourcing: Vec<PathBuf>,  // Script files being run by `source`.
+    pub jobs: Jobs,  // Commands running in the background.
+    saved_state: String,  // The tree as saved in the file, to detect changes.
 }
 
 impl App {
     pub fn new(tree: tree::TreeModel) -> Self {
-        Self {
-            lines: History::new(1000),
+        Self::with_config(tree, Config::default()).unwrap()
+    }
+
+    /// Create an app with the sizes, colors and keymap given by the config.
+    pub fn with_config(tree: tree::TreeModel, config: Config) -> std::io::Result<Self> {
+        let saved_state = serde_json::to_string(&tree.root).unwrap();
+        let palette = Palette::from_config(&config, no_color_requested())?;
+        let jobs = Jobs::new(config.scrollback_size);
+        Ok(Self {
+            lines: History::new(config.scrollback_size),
             buffer: String::new(),
             cursor: Cursor::new(),
             tree,
-            history: History::new(500),
+            history: History::new(config.history_size),
             tab_completion: TabCompleter::new(),
             scroll_pos: 0,
+            highlighter: Highlighter::with_theme(palette.syntax.as_deref()),
+            preview: Preview::new(),
+            browser: Browser::new(),
+            text_area: Rect::default(),
+            search: None,
+            line_selection: None,
+            keymap: Keymap::from_config(&config.keymap)?,
+            kill_ring: KillRing::new(30),
+            last_yank: None,
+            palette,
+            config,
+            last_status: 0,
+            running: None,
+            capture: None,
+            variables: HashMap::new(),
+            sourcing: Vec::new(),
+            jobs,
+            saved_state,
+        })
+    }
+
+    /// True if the tree has been changed since it was loaded.
+    pub fn is_modified(&self) -> bool {
+        serde_json::to_string(&self.tree.root).unwrap() != self.saved_state
+    }
+
+    /// The prompt, made from the prompt template in the config.
+    pub fn prompt(&self) -> RichLine {
+        let state = PromptState {
+            tree: self.tree.root.name.clone(),
+            path: self.tree.path.path.clone(),
+            modified: self.is_modified(),
+            status: self.last_status,
+            count: self.tree.current_item().map(|item| item.iter_children().count()).unwrap_or(0),
+        };
+        state.render(&self.config.prompt, &self.palette)
+    }
+
+    /// The session variables and the built-in variables.
+    pub fn all_variables(&self) -> HashMap<String, String> {
+        let pwd = format!("~/{}", self.tree.pwd());
+        let root = std::env::current_dir().unwrap_or_default();
+        let mut variables = self.variables.clone();
+        variables.insert("VTREE_PWD".to_string(), pwd.trim_end_matches('/').to_string());
+        variables.insert("VTREE_TREE".to_string(), self.tree.root.name.clone());
+        variables.insert("VTREE_ROOT".to_string(), root.to_string_lossy().to_string());
+        variables
+    }
+
+    /// Set a session variable by an assignment such as "NAME=value".
+    pub fn set_variable(&mut self, assignment: &str) -> tree::error::Result<()> {
+        let (name, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| TreeError::new(format!("Expected NAME=value, got {}", assignment)))?;
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(TreeError::new(format!("Invalid variable name: {}", name)));
+        }
+        if _BUILTIN_VARIABLES.contains(&name) {
+            return Err(TreeError::new(format!("{} cannot be set.", name)));
+        }
+        self.variables.insert(name.to_string(), value.to_string());
+        Ok(())
+    }
+
+    /// Options of `call` with all the variables.
+    pub fn call_options(&self, shell: bool, no_resolve: bool) -> CallOptions {
+        CallOptions {
+            shell: if shell { Some(self.config.shell_command()) } else { None },
+            no_resolve,
+            variables: self.all_variables(),
+        }
+    }
+
+    /// Collect the output of the background jobs, and print a notification and a
+    /// new prompt if any job has finished. If a finished job has a redirection, its
+    /// stdout is written to the file. Finished jobs without output are removed.
+    pub fn check_jobs(&mut self) {
+        let finished = self.jobs.poll();
+        if finished.is_empty() {
+            return;
+        }
+        for id in finished {
+            let job = match self.jobs.get_mut(id) {
+                Some(job) => job,
+                None => continue,
+            };
+            let mut result = Ok(());
+            if let Some(redirect) = job.redirect.take() {
+                let text = job.stdout.take().unwrap_or_default();
+                result = virtual_file_candidate(&redirect.target)
+                    .map_err(|err| TreeError::new(format!("{}", err)))
+                    .and_then(|candidate| {
+                        self.tree.write_file(&redirect.target, &text, redirect.append, candidate)
+                    });
+            }
+            let style = if job.status == Some(0) { self.palette.muted } else { self.palette.error };
+            let text = job.describe();
+            if job.output.len() == 0 {
+                self.jobs.remove(id);
+            }
+            self.print_message(text, style);
+            if let Err(err) = result {
+                self.print_error(err);
+            }
         }
+        self.print_prompt();
+    }
+
+    /// Print the prompt in a new line.
+    pub fn print_prompt(&mut self) {
+        let prompt = self.prompt();
+        self.lines.add(prompt);
+        self.scroll_pos = 0;
     }
     
     /// Clear current buffer string and add it to history
@@ -153,24 +320,175 @@ impl App {
 
     pub fn set_buffer(&mut self, buf: String) {
         self.buffer = buf;
-        self.cursor.move_to(self.buffer.len());
+        self.cursor.move_to(self.buffer_len());
         self.tab_completion.seed.clear();
     }
 
+    /// Number of grapheme clusters in the buffer.
+    pub fn buffer_len(&self) -> usize {
+        grapheme_count(&self.buffer)
+    }
+
+    /// Byte offset of the grapheme at `pos` in the buffer.
+    fn byte_offset(&self, pos: usize) -> usize {
+        grapheme_offset(&self.buffer, pos)
+    }
+
+    /// Run `f` with its output captured, and return the result with the captured
+    /// text. The capture of the caller, such as the one of a sourced script in a
+    /// pipeline, is restored afterwards.
+    pub fn capture_output<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> (T, String) {
+        let outer = self.capture.replace(String::new());
+        let result = f(self);
+        let text = std::mem::replace(&mut self.capture, outer).unwrap_or_default();
+        (result, text)
+    }
+
+    /// Add a line of the output. If the output is captured, only the text is
+    /// kept.
+    fn add_line(&mut self, line: RichLine) {
+        match self.capture.as_mut() {
+            Some(capture) => {
+                capture.push_str(&line.raw_text());
+                capture.push('\n');
+            }
+            None => self.lines.add(line),
+        }
+    }
+
     pub fn print_text(&mut self, s: String) {
         s.split("\n").for_each(|s| {
             let mut line = RichLine::new();
-            line.push(RichText::new(s.to_string(), Color::White));
-            self.lines.add(line);
+            line.push(RichText::styled(s.to_string(), self.palette.text));
+            self.add_line(line);
         });
         self.scroll_pos = 0;
     }
 
+    /// Print lines in which the names of items are linked to their paths. Each
+    /// line is given with the name and the absolute virtual path of the item.
+    pub fn print_linked(&mut self, lines: Vec<(String, String, Vec<String>)>) {
+        for (line, name, path) in lines {
+            let mut rline = RichLine::new();
+            match line.find(&name) {
+                Some(idx) if !name.is_empty() => {
+                    let link = format!("~/{}", path.join("/"));
+                    let (head, tail) = (&line[..idx], &line[idx + name.len()..]);
+                    rline.push(RichText::styled(head.to_string(), self.palette.text));
+                    rline.push(RichText::styled(name, self.palette.text).with_link(link));
+                    rline.push(RichText::styled(tail.to_string(), self.palette.text));
+                }
+                _ => rline.push(RichText::styled(line, self.palette.text)),
+            }
+            self.add_line(rline);
+        }
+        self.scroll_pos = 0;
+    }
+
+    /// Print names of the items under the directory at absolute virtual path
+    /// `dir` in a line. Each name is linked to the path of the item.
+    pub fn print_names(&mut self, dir: &[String], names: Vec<String>) {
+        let mut line = RichLine::new();
+        for (idx, name) in names.into_iter().enumerate() {
+            if idx > 0 {
+                line.push(RichText::styled(" ".to_string(), self.palette.text));
+            }
+            let mut path = dir.to_vec();
+            path.push(name.clone());
+            let link = format!("~/{}", path.join("/"));
+            line.push(RichText::styled(name, self.palette.text).with_link(link));
+        }
+        self.add_line(line);
+        self.scroll_pos = 0;
+    }
+
+    /// Print the content of a file. Unless `plain` is true, the content is
+    /// highlighted according to the syntax of the file.
+    pub fn print_file(&mut self, text: &str, path: &Path, plain: bool, number: bool) {
+        if let (Some(capture), false) = (self.capture.as_mut(), number) {
+            // keep the text as it is, such as tabs
+            capture.push_str(text);
+            return;
+        }
+        let lines = if plain {
+            plain_lines(text, self.palette.text)
+        } else {
+            self.highlighter.highlight(text, path, self.palette.text)
+        };
+        let width = lines.len().to_string().len();
+        for (i, line) in lines.into_iter().enumerate() {
+            let line = if number {
+                let num = RichText::styled(format!("{:>width$} ", i + 1, width=width), self.palette.line_number);
+                RichLine::from(vec![num]).join(line)
+            } else {
+                line
+            };
+            self.add_line(line);
+        }
+        self.scroll_pos = 0;
+    }
+
+    /// Print a text in a single style.
+    pub fn print_styled(&mut self, s: String, style: Style) {
+        s.split('\n').for_each(|s| {
+            self.add_line(RichLine::from(vec![RichText::styled(s.to_string(), style)]));
+        });
+        self.scroll_pos = 0;
+    }
+
+    /// Print a message in a single style. Unlike `print_styled`, the message is
+    /// never captured.
+    pub fn print_message(&mut self, s: String, style: Style) {
+        s.split('\n').for_each(|s| {
+            self.lines.add(RichLine::from(vec![RichText::styled(s.to_string(), style)]));
+        });
+        self.scroll_pos = 0;
+    }
+
+    /// Print a line written by a child process. Lines from stderr are printed as
+    /// errors and are never captured.
+    pub fn print_output(&mut self, line: OutputLine) {
+        match line {
+            OutputLine::Stdout(line) => match self.capture.as_mut() {
+                Some(capture) => {
+                    capture.push_str(&line);
+                    capture.push('\n');
+                }
+                None => self.print_styled(sanitize_line(&line), self.palette.text),
+            },
+            OutputLine::Stderr(line) => self.print_message(sanitize_line(&line), self.palette.error),
+        }
+    }
+
+    /// Print a line written by a child process, prefixed by `[name]`.
+    pub fn print_prefixed(&mut self, name: &str, line: OutputLine) {
+        let prefix = format!("[{}] ", name);
+        let (text, style) = match &line {
+            OutputLine::Stdout(text) => (text, self.palette.text),
+            OutputLine::Stderr(text) => (text, self.palette.error),
+        };
+        match (self.capture.as_mut(), &line) {
+            (Some(capture), OutputLine::Stdout(_)) => {
+                capture.push_str(&prefix);
+                capture.push_str(text);
+                capture.push('\n');
+            }
+            _ => {
+                let line = RichLine::from(vec![
+                    RichText::styled(prefix, self.palette.muted),
+                    RichText::styled(sanitize_line(text), style),
+                ]);
+                self.lines.add(line);
+                self.scroll_pos = 0;
+            }
+        }
+    }
+
     pub fn print_error<E: std::error::Error>(&mut self, e: E) {
         let text = format!("{}", e);
         text.split("\n").for_each(|s| {
             let mut line = RichLine::new();
-            line.push(RichText::new(s.to_string(), Color::Red));
+            line.push(RichText::styled(s.to_string(), self.palette.error));
             self.lines.add(line);
         });
         self.scroll_pos = 0
@@ -182,24 +500,23 @@ impl App {
         let nstr = strs.len();
         if nstr == 0 {
             // Show a single space (corresponding to the cursor) if the buffer is empty.
-            let text = RichText::new(" ".to_string(), Color::Black)
-                .restyled(Style::default().bg(Color::Rgb(108, 108, 108)));
+            let text = RichText::styled(" ".to_string(), self.palette.cursor);
             let mut line = RichLine::new();
             line.push(text);
             return line;
         }
         else {
-            let cmd = RichText::new(
+            let cmd = RichText::styled(
                 strs.get(0).unwrap().to_string(), 
-                Color::Yellow
+                self.palette.command
             );
             let mut args = Vec::new();
             args.push(cmd);
             for str in strs[1..].iter() {
                 if str.starts_with("\"") || str.starts_with("\'") {
-                    args.push(RichText::new(str.to_string(), Color::Blue));
+                    args.push(RichText::styled(str.to_string(), self.palette.quoted));
                 } else {
-                    args.push(RichText::new(str.to_string(), Color::White));
+                    args.push(RichText::styled(str.to_string(), self.palette.text));
                 }
             }
 
@@ -207,23 +524,14 @@ impl App {
             // style selected text
             if self.cursor.selection_size() > 0 {
                 let (start, end) = self.cursor.selection();
-                line = line.restyled(
-                    start, end,
-                    Style::default().fg(Color::Black).bg(Color::Rgb(128, 128, 128))
-                );
+                line = line.restyled(start, end, self.palette.selection);
             }
             // style cursor
-            if self.cursor.pos < self.buffer.len() {
-                line = line.restyled(
-                    self.cursor.pos, self.cursor.pos + 1,
-                    Style::default().fg(Color::Black).bg(Color::Rgb(108, 108, 108))
-                );
+            if self.cursor.pos < self.buffer_len() {
+                line = line.restyled(self.cursor.pos, self.cursor.pos + 1, self.palette.cursor);
             } else {
-                line.push(RichText::new(" ".to_string(), Color::Black));
-                line = line.restyled(
-                    self.cursor.pos, self.cursor.pos + 1,
-                    Style::default().fg(Color::Black).bg(Color::Rgb(108, 108, 108))
-                );
+                line.push(RichText::styled(" ".to_string(), Style::default()));
+                line = line.restyled(self.cursor.pos, self.cursor.pos + 1, self.palette.cursor);
             }
             return line;
         }
@@ -239,7 +547,8 @@ impl App {
             return;
         }
         self.cursor.move_to(self.cursor.pos - 1);
-        self.buffer.remove(self.cursor.pos);
+        let (start, end) = (self.byte_offset(self.cursor.pos), self.byte_offset(self.cursor.pos + 1));
+        self.buffer.replace_range(start..end, "");
         self.tab_completion.seed.clear();
     }
 
@@ -249,17 +558,18 @@ impl App {
             self.clear_selected_text();
             return;
         }
-        if self.cursor.pos == self.buffer.len() {
+        if self.cursor.pos == self.buffer_len() {
             return;
         }
-        self.buffer.remove(self.cursor.pos);
+        let (start, end) = (self.byte_offset(self.cursor.pos), self.byte_offset(self.cursor.pos + 1));
+        self.buffer.replace_range(start..end, "");
         self.tab_completion.seed.clear();
     }
 
     pub fn text_move_cursor(&mut self, dx: i16, keep_selection: bool) {
         if 0 <= dx {
             let n = dx as usize;
-            if self.cursor.pos + n <= self.buffer.len() {
+            if self.cursor.pos + n <= self.buffer_len() {
                 if keep_selection {
                     self.cursor.select_to(self.cursor.pos + n);
                 } else {
@@ -286,11 +596,12 @@ impl App {
         let mut pos = self.cursor.pos;
         let mut whitespace_found = false;
         
-        self.buffer[self.cursor.pos..]
-            .chars()
+        self.buffer
+            .graphemes(true)
+            .skip(self.cursor.pos)
             .enumerate()
-            .find(|(i, c)| {
-                if !c.is_whitespace() {
+            .find(|(i, g)| {
+                if !g.chars().all(char::is_whitespace) {
                     pos = self.cursor.pos + i;
                     whitespace_found
                 } else {
@@ -299,7 +610,7 @@ impl App {
                 }
             })  
             .or_else(|| {
-                pos = self.buffer.len();
+                pos = self.buffer_len();
                 None
             });
 
@@ -314,12 +625,12 @@ impl App {
         let mut pos = self.cursor.pos;
         let mut char_found = false;
         
-        self.buffer[..self.cursor.pos]
-            .chars()
+        self.buffer[..self.byte_offset(self.cursor.pos)]
+            .graphemes(true)
             .rev()
             .enumerate()
-            .find(|(i, c)| {
-                if c.is_whitespace() {
+            .find(|(i, g)| {
+                if g.chars().all(char::is_whitespace) {
                     pos = self.cursor.pos - i;
                     char_found
                 } else {
@@ -343,53 +654,300 @@ impl App {
         if self.cursor.selection_size() > 0 {
             self.clear_selected_text();
         }
-        self.buffer.insert(self.cursor.pos, c);
-        self.cursor.move_to(self.cursor.pos + 1);
+        let offset = self.byte_offset(self.cursor.pos);
+        self.buffer.insert(offset, c);
+        // a combining character does not increase the number of graphemes
+        self.cursor.move_to(grapheme_count(&self.buffer[..offset + c.len_utf8()]));
         self.tab_completion.seed.clear();
     }
 
     // Get the selected text.
     pub fn text_selected(&mut self) -> String {
         let (start, end) = self.cursor.selection();
-        self.buffer[start..end].to_string()
+        self.buffer[self.byte_offset(start)..self.byte_offset(end)].to_string()
     }
 
     /// Insert text at the cursor position
     pub fn insert_text(&mut self, text: String) {
-        self.buffer.insert_str(self.cursor.pos, &text);
-        self.cursor.move_to(self.cursor.pos + text.len());
+        let offset = self.byte_offset(self.cursor.pos);
+        self.buffer.insert_str(offset, &text);
+        self.cursor.move_to(grapheme_count(&self.buffer[..offset + text.len()]));
+    }
+
+    /// Remove the selected text and push it to the kill ring.
+    pub fn kill_selected_text(&mut self) {
+        let text = self.text_selected();
+        self.kill_ring.kill(text);
+        self.clear_selected_text();
+        self.tab_completion.seed.clear();
+    }
+
+    /// Kill the text between the cursor and `pos`.
+    pub fn kill_to(&mut self, pos: usize) {
+        self.cursor.clear_selection();
+        self.cursor.select_to(pos);
+        self.kill_selected_text();
+    }
+
+    /// Insert the last killed text at the cursor position.
+    pub fn yank(&mut self) {
+        if let Some(text) = self.kill_ring.yank() {
+            if self.cursor.selection_size() > 0 {
+                self.clear_selected_text();
+            }
+            let start = self.cursor.pos;
+            self.insert_text(text);
+            self.last_yank = Some((start, self.cursor.pos));
+        }
+    }
+
+    /// Replace the text just yanked with the previously killed one.
+    pub fn yank_pop(&mut self) {
+        let (start, end) = match self.last_yank {
+            Some(range) => range,
+            None => return,
+        };
+        if let Some(text) = self.kill_ring.rotate() {
+            self.cursor.set_selection(start, end);
+            self.clear_selected_text();
+            self.insert_text(text);
+            self.last_yank = Some((start, self.cursor.pos));
+        }
     }
 
     fn clear_selected_text(&mut self) {
         let (start, end) = self.cursor.selection();
-        self.buffer.replace_range(start..end, "");
+        let range = self.byte_offset(start)..self.byte_offset(end);
+        self.buffer.replace_range(range, "");
         self.cursor.move_to(start);
     }
 
-    pub fn get_text(&self, nlines: usize) -> Text {
+    /// Get the line at `idx` as displayed. The input buffer is appended to the
+    /// last line.
+    fn display_line(&self, idx: usize) -> RichLine {
+        let mut line = self.decorated_line(idx);
+        if idx + 1 == self.lines.len() && self.running.is_none() {
+            line.extend(self.rich_buffer());
+        }
+        line
+    }
+
+    /// Number of rows that the line at `idx` occupies when wrapped.
+    fn line_rows(&self, idx: usize) -> usize {
+        let width = self.text_area.width.max(1) as usize;
+        self.display_line(idx).width().div_ceil(width).max(1)
+    }
+
+    /// Total number of rows of the wrapped lines.
+    pub fn total_rows(&self) -> usize {
+        (0..self.lines.len()).map(|idx| self.line_rows(idx)).sum()
+    }
+
+    /// Scroll position that is actually used. If the input buffer is longer than
+    /// the text area, rows are scrolled so that the cursor is visible.
+    fn effective_scroll(&self) -> usize {
+        if self.scroll_pos > 0 || self.lines.len() == 0 {
+            return self.scroll_pos;
+        }
+        let width = self.text_area.width.max(1) as usize;
+        let height = self.text_area.height as usize;
+        let last = self.lines.len() - 1;
+        let prefix_width = self.lines[last].width();
+        let cursor_col = prefix_width + display_width(&self.buffer[..self.byte_offset(self.cursor.pos)]);
+        let cursor_row = cursor_col / width;
+        let rows_below_cursor = self.line_rows(last) - 1 - cursor_row;
+        (rows_below_cursor + 1).saturating_sub(height)
+    }
+
+    /// Get the wrapped rows shown in the text area. Each row is given with the
+    /// index of its line and the position of the row in the line.
+    pub fn visible_rows(&self) -> Vec<(usize, usize, RichLine)> {
+        let width = self.text_area.width as usize;
+        let height = self.text_area.height as usize;
+        let scroll = self.effective_scroll();
+        let nrows = scroll.saturating_add(height);
+        let mut rows = Vec::new();
+        for idx in (0..self.lines.len()).rev() {
+            let wrapped = self.display_line(idx).wrap(width);
+            for (nth, row) in wrapped.into_iter().enumerate().rev() {
+                rows.push((idx, nth, row));
+            }
+            if rows.len() >= nrows {
+                break;
+            }
+        }
+        rows.reverse();
+        let stop = rows.len().saturating_sub(scroll);
+        let start = stop.saturating_sub(height);
+        rows.drain(start..stop).collect()
+    }
+
+    pub fn get_text(&self) -> Text<'static> {
         let mut text = Text::from("");
-        let nlines_total = self.lines.len();
-        if nlines_total <= self.scroll_pos || nlines == 0 {
-            return text;
+        text.extend(self.visible_rows().into_iter().map(|(_, _, row)| row.as_spans()));
+        text
+    }
+
+    /// Maximum scroll position.
+    pub fn max_scroll(&self) -> usize {
+        self.total_rows().saturating_sub(self.text_area.height as usize)
+    }
+
+    /// Scroll the output up by `n` rows.
+    pub fn scroll_up(&mut self, n: usize) {
+        self.scroll_pos = self.scroll_pos.saturating_add(n).min(self.max_scroll());
+    }
+
+    /// Scroll the output down by `n` rows.
+    pub fn scroll_down(&mut self, n: usize) {
+        self.scroll_pos = self.scroll_pos.saturating_sub(n);
+    }
+
+    /// Get the line at `idx` with search matches and selection styled.
+    fn decorated_line(&self, idx: usize) -> RichLine {
+        let mut line = self.lines[idx].clone();
+        if let Some(search) = &self.search {
+            let style = if search.current == Some(idx) {
+                self.palette.current_match
+            } else {
+                self.palette.matched
+            };
+            line = search.highlight(&line, style);
         }
-        let stop = nlines_total - self.scroll_pos;
-        let (start, nlines) = if stop >= nlines {
-            (stop - nlines, nlines)
+        if let Some(selection) = &self.line_selection {
+            let len = grapheme_count(&line.raw_text());
+            if selection.contains(idx) && len > 0 {
+                line = line.restyled(0, len, self.palette.selection);
+            }
+        }
+        line
+    }
+
+    /// Title of the console, which shows the current mode.
+    pub fn title(&self) -> String {
+        if let Some(search) = &self.search {
+            let found = if search.current.is_some() || search.query.is_empty() { "" } else { " (not found)" };
+            format!("VTree [search: {}{}]", search.query, found)
+        } else if let Some(selection) = &self.line_selection {
+            format!("VTree [select: {} lines, Enter to copy]", selection.range().count())
+        } else if let Some(name) = &self.running {
+            format!("VTree [running: {}, Ctrl-C to interrupt]", name)
+        } else if self.keymap.mode == EditMode::Normal {
+            "VTree [normal]".to_string()
+        } else if self.jobs.has_running() {
+            let count = self.jobs.iter().filter(|job| job.is_running()).count();
+            format!("VTree [{} background jobs]", count)
         } else {
-            (0, stop)
+            "VTree".to_string()
+        }
+    }
+
+    /// Scroll the output so that the line at `idx` is visible.
+    pub fn scroll_to_line(&mut self, idx: usize) {
+        let h = self.text_area.height as usize;
+        let rows_below: usize = (idx + 1..self.lines.len()).map(|i| self.line_rows(i)).sum();
+        let rows_end = rows_below + self.line_rows(idx);
+        if rows_below < self.scroll_pos {
+            self.scroll_pos = rows_below;
+        } else if rows_end > self.scroll_pos + h {
+            self.scroll_pos = rows_end.saturating_sub(h);
+        }
+    }
+
+    /// Get all the output lines as a plain string.
+    pub fn output_text(&self) -> String {
+        self.lines.iter().map(|line| line.raw_text()).collect::<Vec<String>>().join("\n")
+    }
+
+    /// Process a mouse click at the terminal position (`x`, `y`). Clicking the
+    /// input line moves the cursor and clicking a linked name inserts its path.
+    pub fn click(&mut self, x: u16, y: u16) {
+        let area = self.text_area;
+        if x < area.x || y < area.y || x >= area.right() || y >= area.bottom() {
+            return;
+        }
+        let rows = self.visible_rows();
+        let (idx, nth) = match rows.get((y - area.y) as usize) {
+            Some((idx, nth, _)) => (*idx, *nth),
+            None => return,
         };
-        let mut iter = self.lines.iter().skip(start);
-        if nlines > 1 {
-            for _ in 0..nlines - 1 {
-                let line = iter.next().unwrap();
-                text.extend([line.as_spans()]);
+        let col = nth * area.width as usize + (x - area.x) as usize;
+        if idx + 1 == self.lines.len() {
+            // the input buffer is shown after the last line
+            let prefix_width = self.lines[idx].width();
+            if col >= prefix_width {
+                let mut width = prefix_width;
+                let pos = self.buffer
+                    .graphemes(true)
+                    .take_while(|g| {
+                        width += display_width(g);
+                        width <= col
+                    })
+                    .count();
+                self.cursor.move_to(pos);
             }
+            return;
+        }
+        let link = self.lines[idx].text_at(col).and_then(|text| text.link()).cloned();
+        if let Some(link) = link {
+            self.insert_path(&link);
+        }
+    }
+
+    /// Insert an absolute virtual path (starts with "~/") at the cursor position
+    /// as a path relative to the current directory if possible.
+    fn insert_path(&mut self, link: &str) {
+        let abspath: Vec<String> = link
+            .trim_start_matches('~')
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+            .collect();
+        let curpath = &self.tree.path.path;
+        let path = if abspath.starts_with(curpath) {
+            let relpath = abspath[curpath.len()..].join("/");
+            if relpath.is_empty() { ".".to_string() } else { relpath }
+        } else {
+            link.to_string()
+        };
+        let path = if path.contains(' ') { format!("\"{}\"", path) } else { path };
+        let needs_space = self.buffer[..self.byte_offset(self.cursor.pos)]
+            .chars()
+            .last()
+            .is_some_and(|c| !c.is_whitespace());
+        if needs_space {
+            self.insert_text(format!(" {}", path));
+        } else {
+            self.insert_text(path);
+        }
+    }
+
+    /// Get the word under the cursor with quotations removed.
+    pub fn word_at_cursor(&self) -> Option<String> {
+        let mut pos = 0;
+        for word in parse_string_raw(&self.buffer) {
+            let next_pos = pos + grapheme_count(&word);
+            if word != " " && pos <= self.cursor.pos && self.cursor.pos <= next_pos {
+                return Some(word.trim_matches(['"', '\'']).to_string());
+            }
+            pos = next_pos;
+        }
+        None
+    }
+
+    /// Update the preview pane to show the file under the cursor, or the last
+    /// `cat` target if there is no file under the cursor.
+    pub fn update_preview(&mut self) {
+        let under_cursor = self.word_at_cursor().and_then(|word| {
+            match self.tree.get_item(&word) {
+                Ok(item) if item.is_file() => self.tree.entity_abspath(&word).ok(),
+                _ => None,
+            }
+        });
+        match under_cursor.or_else(|| self.preview.last_target.clone()) {
+            Some(path) => self.preview.load(path, &self.highlighter, &self.palette),
+            None => self.preview.clear(),
         }
-        let mut last_line = iter.next().unwrap().clone();
-        let rbuf = self.rich_buffer();
-        last_line.extend(rbuf);
-        text.extend([last_line.as_spans()]);
-        text
     }
 
     /// Run tab completion and update the buffer.
@@ -406,10 +964,31 @@ impl App {
         }
         if let Some(c) = self.tab_completion.next() {
             self.buffer = [&words[..nwords - 1], &[c]].concat().join("");
-            self.cursor.move_to(self.buffer.len());
+            self.cursor.move_to(self.buffer_len());
         }
         if self.tab_completion.candidates.index == self.tab_completion.candidates.len() {
             self.tab_completion.candidates.index = 0;
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_capture_output() {
+        let tree = tree::TreeModel::from_string(r#"{"name": "t", "children": []}"#);
+        let mut app = App::with_config(tree, Config::default()).unwrap();
+        let (_, outer) = app.capture_output(|app| {
+            app.print_text("a".to_string());
+            // a nested pipeline captures its own output
+            let (_, inner) = app.capture_output(|app| app.print_text("b".to_string()));
+            assert_eq!(inner, "b\n");
+            app.print_text("c".to_string());
+        });
+        assert_eq!(outer, "a\nc\n");
+        assert!(app.capture.is_none());
+        app.print_text("d".to_string());
+        assert_eq!(app.output_text(), "d");
+    }
+}
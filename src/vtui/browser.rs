@@ -0,0 +1,337 @@
+use std::collections::HashSet;
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    text::{Span, Spans},
+    Frame,
+};
+use crossterm::event::{KeyEvent, KeyCode};
+
+use super::super::tree::TreeItem;
+use super::{
+    app::App,
+    session::remove_item,
+    theme::Palette,
+};
+
+const _HELP: &str =
+    "↑↓ move  ←→ collapse/expand  Enter open  r rename  d delete  e describe  o open  F3 shell  q quit";
+
+/// What the browser is waiting for.
+#[derive(PartialEq)]
+pub enum BrowserMode {
+    Normal,
+    Rename,
+    Describe,
+    ConfirmDelete,
+}
+
+/// A visible row of the tree browser.
+pub struct Row {
+    pub path: Vec<String>,  // Absolute virtual path of the item.
+    pub is_dir: bool,
+    pub desc: Option<String>,
+}
+
+impl Row {
+    pub fn name(&self) -> &str {
+        self.path.last().map(|s| s.as_str()).unwrap_or("")
+    }
+
+    /// The virtual path string that can be passed to the tree model.
+    pub fn vpath(&self) -> String {
+        format!("~/{}", self.path.join("/"))
+    }
+}
+
+/// State of the full-screen tree browser.
+pub struct Browser {
+    pub active: bool,
+    pub mode: BrowserMode,
+    expanded: HashSet<Vec<String>>,
+    selected: usize,
+    input: String,
+    message: Option<(String, bool)>,  // Message and whether it is an error.
+}
+
+impl Browser {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            mode: BrowserMode::Normal,
+            expanded: HashSet::new(),
+            selected: 0,
+            input: String::new(),
+            message: None,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.active = !self.active;
+        self.mode = BrowserMode::Normal;
+        self.message = None;
+    }
+
+    /// Collect the rows that are currently visible.
+    pub fn rows(&self, root: &TreeItem) -> Vec<Row> {
+        let mut rows = Vec::new();
+        self.collect_rows(root, &mut Vec::new(), &mut rows);
+        rows
+    }
+
+    fn collect_rows(&self, item: &TreeItem, path: &mut Vec<String>, rows: &mut Vec<Row>) {
+        for child in item.iter_children() {
+            path.push(child.name.clone());
+            let is_dir = child.is_dir();
+            rows.push(Row { path: path.clone(), is_dir, desc: child.desc.clone() });
+            if is_dir && self.expanded.contains(path) {
+                self.collect_rows(child, path, rows);
+            }
+            path.pop();
+        }
+    }
+
+    fn is_expanded(&self, row: &Row) -> bool {
+        self.expanded.contains(&row.path)
+    }
+
+    fn set_message(&mut self, msg: String, is_error: bool) {
+        self.message = Some((msg, is_error));
+    }
+
+    /// Text shown in the status line.
+    fn status_line(&self, palette: &Palette) -> Spans<'static> {
+        match self.mode {
+            BrowserMode::Normal => match &self.message {
+                Some((msg, is_error)) => {
+                    let style = if *is_error { palette.error } else { palette.success };
+                    Spans::from(Span::styled(msg.clone(), style))
+                }
+                None => Spans::from(Span::styled(_HELP, palette.muted)),
+            },
+            BrowserMode::Rename => Spans::from(format!("Rename: {}", self.input)),
+            BrowserMode::Describe => Spans::from(format!("Description: {}", self.input)),
+            BrowserMode::ConfirmDelete => Spans::from("Delete this item? [y/N]"),
+        }
+    }
+}
+
+impl Default for Browser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Process a key event in the browser mode. Returns a command to be run if the
+/// key requests one.
+pub fn process_browser_key(app: &mut App, key: KeyEvent) -> Option<String> {
+    let rows = app.browser.rows(&app.tree.root);
+    let row = rows.get(app.browser.selected);
+    match app.browser.mode {
+        BrowserMode::Normal => {
+            app.browser.message = None;
+            match key.code {
+                KeyCode::Up => {
+                    app.browser.selected = app.browser.selected.saturating_sub(1);
+                }
+                KeyCode::Down if app.browser.selected + 1 < rows.len() => {
+                    app.browser.selected += 1;
+                }
+                KeyCode::Right => {
+                    if let Some(row) = row.filter(|row| row.is_dir) {
+                        app.browser.expanded.insert(row.path.clone());
+                    }
+                }
+                KeyCode::Left => {
+                    if let Some(row) = row {
+                        if !app.browser.expanded.remove(&row.path) {
+                            // select the parent directory
+                            let parent = &row.path[..row.path.len() - 1];
+                            if let Some(idx) = rows.iter().position(|r| r.path == parent) {
+                                app.browser.selected = idx;
+                            }
+                        }
+                    }
+                }
+                KeyCode::Enter => {
+                    if let Some(row) = row {
+                        if row.is_dir {
+                            if !app.browser.expanded.remove(&row.path) {
+                                app.browser.expanded.insert(row.path.clone());
+                            }
+                        } else {
+                            open_row(app, row);
+                        }
+                    }
+                }
+                KeyCode::Char('o') => {
+                    if let Some(row) = row {
+                        open_row(app, row);
+                    }
+                }
+                KeyCode::Char('r') => {
+                    if let Some(row) = row {
+                        app.browser.input = row.name().to_string();
+                        app.browser.mode = BrowserMode::Rename;
+                    }
+                }
+                KeyCode::Char('e') => {
+                    if let Some(row) = row {
+                        app.browser.input = row.desc.clone().unwrap_or_default();
+                        app.browser.mode = BrowserMode::Describe;
+                    }
+                }
+                KeyCode::Char('d') if row.is_some() => {
+                    app.browser.mode = BrowserMode::ConfirmDelete;
+                }
+                KeyCode::F(3) | KeyCode::Esc => app.browser.toggle(),
+                KeyCode::Char('q') => return Some("exit".to_string()),
+                _ => {}
+            }
+        }
+        BrowserMode::Rename | BrowserMode::Describe => {
+            match key.code {
+                KeyCode::Char(c) => app.browser.input.push(c),
+                KeyCode::Backspace => { app.browser.input.pop(); },
+                KeyCode::Esc => app.browser.mode = BrowserMode::Normal,
+                KeyCode::Enter => {
+                    if let Some(row) = row {
+                        if app.browser.mode == BrowserMode::Rename {
+                            rename_row(app, row);
+                        } else {
+                            describe_row(app, row);
+                        }
+                    }
+                    app.browser.mode = BrowserMode::Normal;
+                }
+                _ => {}
+            }
+        }
+        BrowserMode::ConfirmDelete => {
+            if let (KeyCode::Char('y'), Some(row)) = (key.code, row) {
+                match remove_item(&mut app.tree, &row.vpath()) {
+                    Ok(_) => app.browser.set_message(format!("Deleted {}", row.name()), false),
+                    Err(err) => app.browser.set_message(format!("{}", err), true),
+                }
+            }
+            app.browser.mode = BrowserMode::Normal;
+        }
+    }
+    None
+}
+
+fn open_row(app: &mut App, row: &Row) {
+    if let Err(err) = app.tree.open_file(&row.vpath(), app.config.opener.as_deref()) {
+        app.browser.set_message(format!("{}", err), true);
+    }
+}
+
+fn rename_row(app: &mut App, row: &Row) {
+    let name = app.browser.input.clone();
+    if name.is_empty() || name == row.name() {
+        return;
+    }
+    let mut dst = row.path.clone();
+    dst.pop();
+    dst.push(name.clone());
+    let dst = format!("~/{}", dst.join("/"));
+    match app.tree.move_child(&row.vpath(), &dst) {
+        Ok(_) => {
+            let msg = format!("Renamed {} to {}", row.name(), name);
+            app.browser.set_message(msg, false);
+        }
+        Err(err) => app.browser.set_message(format!("{}", err), true),
+    }
+}
+
+fn describe_row(app: &mut App, row: &Row) {
+    let desc = app.browser.input.clone();
+    let desc = if desc.is_empty() { None } else { Some(desc) };
+    if let Err(err) = app.tree.set_description(&row.vpath(), desc) {
+        app.browser.set_message(format!("{}", err), true);
+    }
+}
+
+pub fn render_browser<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(f.size());
+
+    let rows = app.browser.rows(&app.tree.root);
+    if app.browser.selected >= rows.len() {
+        app.browser.selected = rows.len().saturating_sub(1);
+    }
+    let items: Vec<ListItem> = rows
+        .iter()
+        .map(|row| {
+            let indent = "  ".repeat(row.path.len() - 1);
+            let (marker, style) = if !row.is_dir {
+                ("  ", app.palette.text)
+            } else if app.browser.is_expanded(row) {
+                ("▾ ", app.palette.directory)
+            } else {
+                ("▸ ", app.palette.directory)
+            };
+            let mut spans = vec![
+                Span::raw(indent),
+                Span::styled(marker, style),
+                Span::styled(row.name().to_string(), style),
+            ];
+            if let Some(desc) = &row.desc {
+                spans.push(Span::styled(format!("  {}", desc), app.palette.muted));
+            }
+            ListItem::new(Spans::from(spans))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).border_style(app.palette.border)
+        .title(format!("VTree: {}", app.tree.root.name)))
+        .highlight_style(app.palette.highlight);
+    let mut state = ListState::default();
+    if !rows.is_empty() {
+        state.select(Some(app.browser.selected));
+    }
+    f.render_stateful_widget(list, chunks[0], &mut state);
+    f.render_widget(Paragraph::new(app.browser.status_line(&app.palette)), chunks[1]);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const JSON: &str = r#"{
+        "name": "test",
+        "children": [
+            {
+                "name": "dir-A",
+                "children": [
+                    {"name": "sub-dir", "children": [], "desc": null, "entity": null}
+                ],
+                "desc": "A",
+                "entity": null
+            },
+            {"name": "dir-B", "children": [], "desc": null, "entity": null}
+        ],
+        "desc": null,
+        "entity": null
+    }"#;
+
+    #[test]
+    fn test_rows_expanded() {
+        let root = TreeItem::from_string(&JSON.to_string());
+        let mut browser = Browser::new();
+        let rows = browser.rows(&root);
+        assert_eq!(rows.iter().map(|r| r.name()).collect::<Vec<_>>(), vec!["dir-A", "dir-B"]);
+        assert_eq!(rows[0].desc, Some("A".to_string()));
+
+        browser.expanded.insert(vec!["dir-A".to_string()]);
+        let rows = browser.rows(&root);
+        assert_eq!(
+            rows.iter().map(|r| r.vpath()).collect::<Vec<_>>(),
+            vec!["~/dir-A", "~/dir-A/sub-dir", "~/dir-B"]
+        );
+    }
+}
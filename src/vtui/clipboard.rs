@@ -0,0 +1,81 @@
+use std::collections::VecDeque;
+use arboard::Clipboard;
+
+pub fn get_text() -> String {
+    match Clipboard::new() {
+        Ok(mut clip) => clip.get_text().unwrap_or("".to_string()),
+        Err(_) => "".to_string()
+    }
+}
+
+pub fn set_text(text: &String) {
+    if let Ok(mut clip) = Clipboard::new() {
+        clip.set_text(text).unwrap_or(());
+    }
+}
+
+/// Ring of killed texts, as in emacs and readline.
+pub struct KillRing {
+    ring: VecDeque<String>,
+    index: usize,  // Index of the text that will be yanked.
+    max: usize,
+}
+
+impl KillRing {
+    pub fn new(max: usize) -> Self {
+        Self { ring: VecDeque::new(), index: 0, max }
+    }
+
+    /// Push a killed text to the ring.
+    pub fn kill(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        self.ring.push_front(text);
+        self.ring.truncate(self.max);
+        self.index = 0;
+    }
+
+    /// Get the text to be yanked.
+    pub fn yank(&self) -> Option<String> {
+        self.ring.get(self.index).cloned()
+    }
+
+    /// Rotate the ring and get the previously killed text.
+    pub fn rotate(&mut self) -> Option<String> {
+        if self.ring.is_empty() {
+            return None;
+        }
+        self.index = (self.index + 1) % self.ring.len();
+        self.yank()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_kill_ring() {
+        let mut ring = KillRing::new(2);
+        assert_eq!(ring.yank(), None);
+        ring.kill("a".to_string());
+        ring.kill("".to_string());
+        ring.kill("b".to_string());
+        assert_eq!(ring.yank(), Some("b".to_string()));
+        assert_eq!(ring.rotate(), Some("a".to_string()));
+        assert_eq!(ring.rotate(), Some("b".to_string()));
+        ring.kill("c".to_string());
+        assert_eq!(ring.len(), 2);
+        assert_eq!(ring.yank(), Some("c".to_string()));
+        assert_eq!(ring.rotate(), Some("b".to_string()));
+    }
+}
@@ -0,0 +1,155 @@
+use std::path::Path;
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{FontStyle, Style as SyntectStyle, Theme, ThemeSet},
+    parsing::{SyntaxReference, SyntaxSet},
+    util::LinesWithEndings,
+};
+use tui::style::{Color, Modifier, Style};
+use super::rich::{RichText, RichLine};
+
+const _THEME: &str = "base16-ocean.dark";
+const _TAB: &str = "    ";
+
+/// Syntax highlighter that converts file contents into RichLines.
+pub struct Highlighter {
+    syntaxes: SyntaxSet,
+    theme: Option<Theme>,  // None if highlighting is disabled.
+}
+
+impl Highlighter {
+    pub fn new() -> Self {
+        Self::with_theme(Some(_THEME))
+    }
+
+    /// Create a highlighter with a syntect theme, such as "InspiredGitHub". Files
+    /// are not highlighted if `theme` is None.
+    pub fn with_theme(theme: Option<&str>) -> Self {
+        let syntaxes = SyntaxSet::load_defaults_newlines();
+        let theme = theme.map(|name| {
+            let mut themes = ThemeSet::load_defaults();
+            themes.themes.remove(name).unwrap_or_default()
+        });
+        Self { syntaxes, theme }
+    }
+
+    /// Find the syntax definition of a file by its extension, or by its first
+    /// line (such as a shebang) if the extension is not known.
+    fn find_syntax(&self, path: &Path, text: &str) -> &SyntaxReference {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.syntaxes.find_syntax_by_extension(ext))
+            .or_else(|| {
+                let first_line = text.lines().next().unwrap_or("");
+                self.syntaxes.find_syntax_by_first_line(first_line)
+            })
+            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text())
+    }
+
+    /// Highlight `text` as the content of the file at `path`. Lines are styled
+    /// with `plain` if highlighting is disabled or fails.
+    pub fn highlight(&self, text: &str, path: &Path, plain: Style) -> Vec<RichLine> {
+        let theme = match &self.theme {
+            Some(theme) => theme,
+            None => return plain_lines(text, plain),
+        };
+        let syntax = self.find_syntax(path, text);
+        let mut highlighter = HighlightLines::new(syntax, theme);
+        let mut lines = Vec::new();
+        for line in LinesWithEndings::from(text) {
+            let mut rline = RichLine::new();
+            match highlighter.highlight_line(line, &self.syntaxes) {
+                Ok(regions) => {
+                    for (style, s) in regions {
+                        rline.push(RichText::styled(strip_line(s), as_tui_style(style)));
+                    }
+                }
+                Err(_) => {
+                    rline.push(RichText::styled(strip_line(line), plain));
+                }
+            }
+            lines.push(rline);
+        }
+        lines
+    }
+}
+
+impl Default for Highlighter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Convert plain text into RichLines with a single style.
+pub fn plain_lines(text: &str, style: Style) -> Vec<RichLine> {
+    text.lines()
+        .map(|line| {
+            let mut rline = RichLine::new();
+            rline.push(RichText::styled(strip_line(line), style));
+            rline
+        })
+        .collect()
+}
+
+/// Remove line endings and expand tabs so that the text is rendered correctly.
+fn strip_line(s: &str) -> String {
+    s.trim_end_matches(['\n', '\r']).replace('\t', _TAB)
+}
+
+/// Convert a syntect style into a tui style.
+fn as_tui_style(style: SyntectStyle) -> Style {
+    let fg = style.foreground;
+    let mut out = Style::default().fg(Color::Rgb(fg.r, fg.g, fg.b));
+    if style.font_style.contains(FontStyle::BOLD) {
+        out = out.add_modifier(Modifier::BOLD);
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        out = out.add_modifier(Modifier::ITALIC);
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        out = out.add_modifier(Modifier::UNDERLINED);
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_highlight_keeps_text() {
+        let highlighter = Highlighter::new();
+        let text = "fn main() {\n\tprintln!(\"Hello\");\n}\n";
+        let lines = highlighter.highlight(text, Path::new("main.rs"), Style::default());
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].raw_text(), "fn main() {");
+        assert_eq!(lines[1].raw_text(), "    println!(\"Hello\");");
+        assert_eq!(lines[2].raw_text(), "}");
+    }
+
+    #[test]
+    fn test_find_syntax_by_first_line() {
+        let highlighter = Highlighter::new();
+        let syntax = highlighter.find_syntax(Path::new("script"), "#!/usr/bin/env python\n");
+        assert_eq!(syntax.name, "Python");
+        let syntax = highlighter.find_syntax(Path::new("notes"), "some text\n");
+        assert_eq!(syntax.name, "Plain Text");
+    }
+
+    #[test]
+    fn test_highlight_unknown_extension() {
+        let highlighter = Highlighter::new();
+        let lines = highlighter.highlight("a\nb", Path::new("file.unknown-ext"), Style::default());
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].raw_text(), "b");
+    }
+
+    #[test]
+    fn test_highlight_disabled() {
+        let highlighter = Highlighter::with_theme(None);
+        let plain = Style::default().fg(Color::Red);
+        let lines = highlighter.highlight("fn main() {}", Path::new("main.rs"), plain);
+        assert_eq!(lines[0].as_spans().0.len(), 1);
+        assert_eq!(lines[0].as_spans().0[0].style, plain);
+    }
+}
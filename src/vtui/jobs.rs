@@ -0,0 +1,219 @@
+use std::process::Command;
+
+use super::{
+    history::History,
+    process::{CapturedChild, OutputLine},
+    super::terminal::Redirect,
+};
+
+/// A command running in the background.
+pub struct Job {
+    pub id: usize,
+    pub name: String,  // The command line.
+    pub output: History<OutputLine>,  // Output that has not been shown yet.
+    pub stdout: Option<String>,  // Whole stdout of a job with a redirection, not to be truncated.
+    pub status: Option<i32>,  // Exit code, or None if the job is running.
+    pub killed: bool,
+    pub redirect: Option<Redirect>,  // File to which the stdout is written when the job finishes.
+    pub child: CapturedChild,
+}
+
+impl Job {
+    /// Collect the output of the job. Returns true if the job has just finished.
+    fn poll(&mut self) -> bool {
+        if self.status.is_some() {
+            return false;
+        }
+        for line in self.child.read_lines() {
+            match (line, self.stdout.as_mut()) {
+                (OutputLine::Stdout(text), Some(stdout)) => {
+                    stdout.push_str(&text);
+                    stdout.push('\n');
+                }
+                (line, _) => self.output.add(line),
+            }
+        }
+        match self.child.try_finish() {
+            Ok(None) => false,
+            Ok(Some(status)) => {
+                self.status = Some(status.code().unwrap_or(if self.killed { 130 } else { 1 }));
+                true
+            }
+            Err(_) => {
+                self.status = Some(1);
+                true
+            }
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.status.is_none()
+    }
+
+    /// Kill the job. The status is updated when the job is polled.
+    pub fn kill(&mut self) -> std::io::Result<()> {
+        self.killed = true;
+        self.child.kill()
+    }
+
+    /// Describe the state of the job, such as "[1] running: call make".
+    pub fn describe(&self) -> String {
+        let state = match self.status {
+            None => "running".to_string(),
+            Some(_) if self.killed => "killed".to_string(),
+            Some(0) => "done".to_string(),
+            Some(code) => format!("exit status {}", code),
+        };
+        let mut text = format!("[{}] {}: {}", self.id, state, self.name);
+        if self.output.len() > 0 {
+            text.push_str(&format!(" ({} lines, `fg %{}` to show)", self.output.len(), self.id));
+        }
+        text
+    }
+}
+
+/// Background jobs of the virtual terminal. Jobs are numbered from 1.
+pub struct Jobs {
+    jobs: Vec<Job>,
+    next_id: usize,
+    max_lines: usize,  // Number of output lines kept for each job.
+}
+
+impl Jobs {
+    pub fn new(max_lines: usize) -> Self {
+        Self { jobs: Vec::new(), next_id: 1, max_lines }
+    }
+
+    /// Start a command in the background and return the job ID. If `redirect` is
+    /// given, the stdout is kept apart from the output buffer to be written to the
+    /// file.
+    pub fn spawn(
+        &mut self, name: String, cmd: Command, redirect: Option<Redirect>
+    ) -> std::io::Result<usize> {
+        let child = CapturedChild::spawn(cmd, None)?;
+        let id = self.next_id;
+        self.next_id += 1;
+        self.jobs.push(Job {
+            id,
+            name,
+            output: History::new(self.max_lines),
+            stdout: redirect.as_ref().map(|_| String::new()),
+            status: None,
+            killed: false,
+            redirect,
+            child,
+        });
+        Ok(id)
+    }
+
+    /// Collect the output of the running jobs. Returns the IDs of the jobs that
+    /// have just finished.
+    pub fn poll(&mut self) -> Vec<usize> {
+        self.jobs
+            .iter_mut()
+            .filter_map(|job| if job.poll() { Some(job.id) } else { None })
+            .collect()
+    }
+
+    pub fn has_running(&self) -> bool {
+        self.jobs.iter().any(|job| job.is_running())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Job> {
+        self.jobs.iter()
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|job| job.id == id)
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<Job> {
+        let idx = self.jobs.iter().position(|job| job.id == id)?;
+        Some(self.jobs.remove(idx))
+    }
+
+    /// ID of the latest job.
+    pub fn last_id(&self) -> Option<usize> {
+        self.jobs.last().map(|job| job.id)
+    }
+
+    /// Kill all the running jobs.
+    pub fn kill_all(&mut self) {
+        for job in self.jobs.iter_mut().filter(|job| job.is_running()) {
+            let _ = job.kill();
+        }
+    }
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        Self::new(1000)
+    }
+}
+
+/// Parse a job specification such as "%1" or "1".
+pub fn parse_job_spec(spec: &str) -> Option<usize> {
+    spec.strip_prefix('%').unwrap_or(spec).parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_job_spec() {
+        assert_eq!(parse_job_spec("%2"), Some(2));
+        assert_eq!(parse_job_spec("3"), Some(3));
+        assert_eq!(parse_job_spec("%x"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_jobs() {
+        let mut jobs = Jobs::new(10);
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo out; echo err 1>&2"]);
+        let id = jobs.spawn("call sh".to_string(), cmd, None).unwrap();
+        let mut cmd = Command::new("sleep");
+        cmd.arg("10");
+        let sleep = jobs.spawn("call sleep".to_string(), cmd, None).unwrap();
+        assert_eq!(jobs.last_id(), Some(sleep));
+        let mut finished = Vec::new();
+        while !finished.contains(&id) {
+            finished.extend(jobs.poll());
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(jobs.has_running());
+        let job = jobs.get_mut(id).unwrap();
+        assert_eq!(job.status, Some(0));
+        assert_eq!(job.stdout, None);
+        assert_eq!(job.output.len(), 2);
+
+        jobs.kill_all();
+        while jobs.has_running() {
+            jobs.poll();
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(jobs.remove(sleep).unwrap().describe().starts_with("[2] killed"));
+        assert!(jobs.remove(sleep).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_jobs_redirect() {
+        // the redirected stdout is not limited by the size of the output buffer
+        let mut jobs = Jobs::new(10);
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "seq 1 100; echo err 1>&2"]);
+        let redirect = Redirect { target: "log.txt".to_string(), append: false };
+        let id = jobs.spawn("call sh".to_string(), cmd, Some(redirect)).unwrap();
+        while jobs.has_running() {
+            jobs.poll();
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let job = jobs.get_mut(id).unwrap();
+        let expected: String = (1..=100).map(|n| format!("{}\n", n)).collect();
+        assert_eq!(job.stdout.as_deref(), Some(expected.as_str()));
+        assert_eq!(job.output.history.iter().collect::<Vec<_>>(), [&OutputLine::Stderr("err".to_string())]);
+    }
+}
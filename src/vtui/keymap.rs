@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+use crossterm::event::{KeyCode, KeyModifiers};
+use serde::{Serialize, Deserialize};
+
+/// Actions that can be bound to keys in the line editor.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Submit,
+    Complete,
+    ClearBuffer,
+    MoveLeft,
+    MoveRight,
+    MoveWordLeft,
+    MoveWordRight,
+    MoveHome,
+    MoveEnd,
+    SelectLeft,
+    SelectRight,
+    SelectWordLeft,
+    SelectWordRight,
+    SelectHome,
+    SelectEnd,
+    SelectAll,
+    DeleteBackward,
+    DeleteForward,
+    DeleteForwardOrExit,  // Delete the next character, or exit if the buffer is empty.
+    KillLineBackward,
+    KillLineForward,
+    KillWordBackward,
+    KillWordForward,
+    Yank,
+    YankPop,
+    Copy,
+    Cut,
+    Paste,
+    HistoryPrev,
+    HistoryNext,
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    ScrollTop,
+    ScrollBottom,
+    Search,
+    SelectLines,
+    TogglePreview,
+    ToggleBrowser,
+    NormalMode,
+    InsertMode,
+    InsertAfter,
+    InsertAtStart,
+    InsertAtEnd,
+}
+
+/// Editing mode of the line editor. The normal mode is only used by the vi preset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditMode {
+    Insert,
+    Normal,
+}
+
+// Bindings shared by all the presets.
+const _COMMON: &[(&str, Action)] = &[
+    ("enter", Action::Submit),
+    ("f2", Action::TogglePreview),
+    ("f3", Action::ToggleBrowser),
+    ("left", Action::MoveLeft),
+    ("right", Action::MoveRight),
+    ("ctrl-left", Action::MoveWordLeft),
+    ("ctrl-right", Action::MoveWordRight),
+    ("home", Action::MoveHome),
+    ("end", Action::MoveEnd),
+    ("up", Action::HistoryPrev),
+    ("down", Action::HistoryNext),
+    ("shift-up", Action::ScrollUp),
+    ("shift-down", Action::ScrollDown),
+    ("pageup", Action::PageUp),
+    ("pagedown", Action::PageDown),
+    ("ctrl-home", Action::ScrollTop),
+    ("ctrl-end", Action::ScrollBottom),
+    ("ctrl-f", Action::Search),
+    ("ctrl-s", Action::SelectLines),
+    ("ctrl-c", Action::Copy),
+    ("ctrl-v", Action::Paste),
+];
+
+// Bindings of the default preset, used in addition to the common ones.
+const _DEFAULT: &[(&str, Action)] = &[
+    ("backspace", Action::DeleteBackward),
+    ("delete", Action::DeleteForward),
+    ("tab", Action::Complete),
+    ("esc", Action::ClearBuffer),
+    ("shift-left", Action::SelectLeft),
+    ("shift-right", Action::SelectRight),
+    ("ctrl-shift-left", Action::SelectWordLeft),
+    ("ctrl-shift-right", Action::SelectWordRight),
+    ("shift-home", Action::SelectHome),
+    ("shift-end", Action::SelectEnd),
+    ("ctrl-a", Action::SelectAll),
+    ("ctrl-x", Action::Cut),
+    ("ctrl-w", Action::KillWordBackward),
+    ("alt-backspace", Action::KillWordBackward),
+    ("alt-d", Action::KillWordForward),
+    ("ctrl-u", Action::KillLineBackward),
+    ("ctrl-k", Action::KillLineForward),
+    ("ctrl-y", Action::Yank),
+    ("alt-y", Action::YankPop),
+    ("alt-b", Action::MoveWordLeft),
+    ("alt-f", Action::MoveWordRight),
+    ("ctrl-d", Action::DeleteForwardOrExit),
+];
+
+// Emacs/readline bindings, overriding the default ones.
+const _EMACS: &[(&str, Action)] = &[
+    ("ctrl-a", Action::MoveHome),
+    ("ctrl-e", Action::MoveEnd),
+    ("ctrl-b", Action::MoveLeft),
+    ("ctrl-f", Action::MoveRight),
+    ("ctrl-p", Action::HistoryPrev),
+    ("ctrl-n", Action::HistoryNext),
+    ("ctrl-h", Action::DeleteBackward),
+    ("ctrl-g", Action::ClearBuffer),
+    ("ctrl-r", Action::Search),
+];
+
+// Vi insert mode bindings, overriding the default ones.
+const _VI_INSERT: &[(&str, Action)] = &[
+    ("esc", Action::NormalMode),
+];
+
+// Vi normal mode bindings, used in addition to the common ones.
+const _VI_NORMAL: &[(&str, Action)] = &[
+    ("h", Action::MoveLeft),
+    ("l", Action::MoveRight),
+    ("b", Action::MoveWordLeft),
+    ("w", Action::MoveWordRight),
+    ("0", Action::MoveHome),
+    ("$", Action::MoveEnd),
+    ("k", Action::HistoryPrev),
+    ("j", Action::HistoryNext),
+    ("x", Action::DeleteForward),
+    ("X", Action::DeleteBackward),
+    ("D", Action::KillLineForward),
+    ("p", Action::Yank),
+    ("i", Action::InsertMode),
+    ("a", Action::InsertAfter),
+    ("I", Action::InsertAtStart),
+    ("A", Action::InsertAtEnd),
+    ("/", Action::Search),
+    ("v", Action::SelectLines),
+    ("tab", Action::Complete),
+    ("esc", Action::ClearBuffer),
+];
+
+type KeyBindings = HashMap<(KeyCode, KeyModifiers), Action>;
+
+/// Table that maps keys to the actions of the line editor.
+pub struct Keymap {
+    pub mode: EditMode,
+    insert: KeyBindings,
+    normal: KeyBindings,
+}
+
+/// Keymap configuration, which is a part of the vtree config.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct KeymapConfig {
+    #[serde(default = "default_preset")]
+    pub preset: String,  // Name of the preset to start from.
+    #[serde(default)]
+    pub bindings: HashMap<String, Action>,  // Additional bindings in the insert mode.
+    #[serde(default)]
+    pub normal_bindings: HashMap<String, Action>,  // Additional bindings in the vi normal mode.
+}
+
+fn default_preset() -> String {
+    "default".to_string()
+}
+
+impl Default for KeymapConfig {
+    fn default() -> Self {
+        Self { preset: default_preset(), bindings: HashMap::new(), normal_bindings: HashMap::new() }
+    }
+}
+
+fn invalid_input(msg: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, msg)
+}
+
+impl Keymap {
+    fn from_tables(insert: &[&[(&str, Action)]], normal: &[&[(&str, Action)]]) -> Self {
+        let mut keymap = Self { mode: EditMode::Insert, insert: HashMap::new(), normal: HashMap::new() };
+        for (spec, action) in insert.iter().flat_map(|table| table.iter()) {
+            keymap.insert.insert(parse_key(spec).unwrap(), *action);
+        }
+        for (spec, action) in normal.iter().flat_map(|table| table.iter()) {
+            keymap.normal.insert(parse_key(spec).unwrap(), *action);
+        }
+        keymap
+    }
+
+    /// Create a keymap from a preset name, one of "default", "emacs" or "vi".
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::from_tables(&[_COMMON, _DEFAULT], &[])),
+            "emacs" => Some(Self::from_tables(&[_COMMON, _DEFAULT, _EMACS], &[])),
+            "vi" => Some(Self::from_tables(&[_COMMON, _DEFAULT, _VI_INSERT], &[_COMMON, _VI_NORMAL])),
+            _ => None,
+        }
+    }
+
+    pub fn from_config(config: &KeymapConfig) -> std::io::Result<Self> {
+        let mut keymap = Self::preset(&config.preset)
+            .ok_or_else(|| invalid_input(format!("Unknown keymap preset: {}", config.preset)))?;
+        for (spec, action) in &config.bindings {
+            keymap.bind(spec, *action, EditMode::Insert)?;
+        }
+        for (spec, action) in &config.normal_bindings {
+            keymap.bind(spec, *action, EditMode::Normal)?;
+        }
+        Ok(keymap)
+    }
+
+    /// Bind the key described by `spec` (such as "ctrl-k") to an action.
+    pub fn bind(&mut self, spec: &str, action: Action, mode: EditMode) -> std::io::Result<()> {
+        let key = parse_key(spec).ok_or_else(|| invalid_input(format!("Invalid key: {}", spec)))?;
+        match mode {
+            EditMode::Insert => self.insert.insert(key, action),
+            EditMode::Normal => self.normal.insert(key, action),
+        };
+        Ok(())
+    }
+
+    /// Get the action bound to the key in the current mode.
+    pub fn action(&self, code: KeyCode, modifiers: KeyModifiers) -> Option<Action> {
+        let key = normalize_key(code, modifiers);
+        match self.mode {
+            EditMode::Insert => self.insert.get(&key).copied(),
+            EditMode::Normal => self.normal.get(&key).copied(),
+        }
+    }
+
+    /// True if unbound characters are inserted into the buffer.
+    pub fn inserts_text(&self) -> bool {
+        self.mode == EditMode::Insert
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::preset("default").unwrap()
+    }
+}
+
+/// Characters carry the case themselves, so the shift modifier is ignored.
+fn normalize_key(code: KeyCode, modifiers: KeyModifiers) -> (KeyCode, KeyModifiers) {
+    match code {
+        KeyCode::Char(c) => {
+            let c = if modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+                c.to_ascii_lowercase()
+            } else {
+                c
+            };
+            (KeyCode::Char(c), modifiers - KeyModifiers::SHIFT)
+        }
+        KeyCode::BackTab => (KeyCode::Tab, modifiers | KeyModifiers::SHIFT),
+        _ => (code, modifiers),
+    }
+}
+
+/// Parse a key description such as "ctrl-shift-left", "alt-b" or "$".
+pub fn parse_key(spec: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = spec;
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        let (modifier, len) = if lower.starts_with("ctrl-") && rest.len() > 5 {
+            (KeyModifiers::CONTROL, 5)
+        } else if lower.starts_with("alt-") && rest.len() > 4 {
+            (KeyModifiers::ALT, 4)
+        } else if lower.starts_with("shift-") && rest.len() > 6 {
+            (KeyModifiers::SHIFT, 6)
+        } else {
+            break;
+        };
+        modifiers |= modifier;
+        rest = &rest[len..];
+    }
+    let code = match rest.to_ascii_lowercase().as_str() {
+        "enter" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "esc" => KeyCode::Esc,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "space" => KeyCode::Char(' '),
+        lower => {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => {
+                    let n = lower.strip_prefix('f')?.parse::<u8>().ok()?;
+                    KeyCode::F(n)
+                }
+            }
+        }
+    };
+    Some(normalize_key(code, modifiers))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(parse_key("enter"), Some((KeyCode::Enter, KeyModifiers::NONE)));
+        assert_eq!(parse_key("Ctrl-K"), Some((KeyCode::Char('k'), KeyModifiers::CONTROL)));
+        assert_eq!(
+            parse_key("ctrl-shift-left"),
+            Some((KeyCode::Left, KeyModifiers::CONTROL | KeyModifiers::SHIFT))
+        );
+        assert_eq!(parse_key("A"), Some((KeyCode::Char('A'), KeyModifiers::NONE)));
+        assert_eq!(parse_key("ctrl--"), Some((KeyCode::Char('-'), KeyModifiers::CONTROL)));
+        assert_eq!(parse_key("f12"), Some((KeyCode::F(12), KeyModifiers::NONE)));
+        assert_eq!(parse_key("ctrl-foo"), None);
+    }
+
+    #[test]
+    fn test_presets() {
+        let keymap = Keymap::preset("default").unwrap();
+        assert_eq!(keymap.action(KeyCode::Char('a'), KeyModifiers::CONTROL), Some(Action::SelectAll));
+        assert_eq!(keymap.action(KeyCode::Char('u'), KeyModifiers::CONTROL), Some(Action::KillLineBackward));
+        assert_eq!(keymap.action(KeyCode::Char('a'), KeyModifiers::NONE), None);
+
+        let keymap = Keymap::preset("emacs").unwrap();
+        assert_eq!(keymap.action(KeyCode::Char('a'), KeyModifiers::CONTROL), Some(Action::MoveHome));
+
+        let mut keymap = Keymap::preset("vi").unwrap();
+        assert_eq!(keymap.action(KeyCode::Esc, KeyModifiers::NONE), Some(Action::NormalMode));
+        keymap.mode = EditMode::Normal;
+        assert_eq!(keymap.action(KeyCode::Char('A'), KeyModifiers::SHIFT), Some(Action::InsertAtEnd));
+        assert_eq!(keymap.action(KeyCode::Char('q'), KeyModifiers::NONE), None);
+        assert!(!keymap.inserts_text());
+
+        assert!(Keymap::preset("unknown").is_none());
+    }
+
+    #[test]
+    fn test_from_config() {
+        let json = r#"{"preset": "emacs", "bindings": {"alt-k": "kill-line-backward"}}"#;
+        let config: KeymapConfig = serde_json::from_str(json).unwrap();
+        let keymap = Keymap::from_config(&config).unwrap();
+        assert_eq!(keymap.action(KeyCode::Char('k'), KeyModifiers::ALT), Some(Action::KillLineBackward));
+        assert_eq!(keymap.action(KeyCode::Char('e'), KeyModifiers::CONTROL), Some(Action::MoveEnd));
+
+        let json = r#"{"bindings": {"hyper-k": "yank"}}"#;
+        let config: KeymapConfig = serde_json::from_str(json).unwrap();
+        assert!(Keymap::from_config(&config).is_err());
+    }
+}
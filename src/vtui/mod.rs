@@ -3,5 +3,15 @@ pub mod vtui;
 pub mod session;
 pub mod history;
 pub mod app;
+pub mod highlight;
+pub mod preview;
+pub mod browser;
+pub mod scrollback;
+pub mod keymap;
+pub mod clipboard;
+pub mod theme;
+pub mod prompt;
+pub mod process;
+pub mod jobs;
 
-pub use session::enter;
\ No newline at end of file
+pub use session::{enter, browse};
\ No newline at end of file
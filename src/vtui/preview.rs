@@ -0,0 +1,127 @@
+use std::{
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tui::text::Text;
+use super::{
+    highlight::Highlighter,
+    rich::{RichText, RichLine},
+    theme::Palette,
+};
+
+// Maximum number of lines read from the previewed file.
+const _MAX_LINES: usize = 500;
+
+/// State of the file preview pane.
+pub struct Preview {
+    pub visible: bool,
+    pub last_target: Option<PathBuf>,  // The last target of `cat`.
+    path: Option<PathBuf>,  // The file currently shown.
+    stamp: Option<(SystemTime, u64)>,  // Modification time and size of the file when it was read.
+    lines: Vec<RichLine>,
+}
+
+impl Preview {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            last_target: None,
+            path: None,
+            stamp: None,
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Load the file at `path` if it is not shown yet, or if it has been changed
+    /// since it was read, such as by `edit` or a redirect.
+    pub fn load(&mut self, path: PathBuf, highlighter: &Highlighter, palette: &Palette) {
+        let stamp = file_stamp(&path);
+        if self.path.as_ref() == Some(&path) && self.stamp == stamp {
+            return;
+        }
+        self.lines = match read_head(&path) {
+            Ok(text) => highlighter.highlight(&text, &path, palette.text),
+            Err(err) => {
+                let mut line = RichLine::new();
+                line.push(RichText::styled(format!("{}", err), palette.error));
+                vec![line]
+            }
+        };
+        self.path = Some(path);
+        self.stamp = stamp;
+    }
+
+    /// Clear the preview content.
+    pub fn clear(&mut self) {
+        self.path = None;
+        self.stamp = None;
+        self.lines.clear();
+    }
+
+    pub fn title(&self) -> String {
+        match &self.path {
+            Some(path) => {
+                let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+                format!("Preview: {}", name)
+            }
+            None => "Preview".to_string(),
+        }
+    }
+
+    pub fn get_text(&self, nlines: usize) -> Text<'static> {
+        let mut text = Text::from("");
+        text.extend(self.lines.iter().take(nlines).map(|line| line.as_spans()));
+        text
+    }
+}
+
+impl Default for Preview {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Modification time and size of the file at `path`, or None if it cannot be read.
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Read the first lines of a text file.
+fn read_head(path: &Path) -> std::io::Result<String> {
+    let file = std::fs::File::open(path)?;
+    let mut text = String::new();
+    for line in BufReader::new(file).lines().take(_MAX_LINES) {
+        text.push_str(&line?);
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load_changed_file() {
+        let dir = std::env::current_dir().unwrap().join("target/test-preview-load");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.txt");
+        std::fs::write(&path, "old\n").unwrap();
+        let (highlighter, palette) = (Highlighter::with_theme(None), Palette::default());
+        let mut preview = Preview::new();
+        preview.load(path.clone(), &highlighter, &palette);
+        assert_eq!(preview.lines[0].raw_text(), "old");
+
+        // the file is read again once its size or modification time changes
+        std::fs::write(&path, "changed\n").unwrap();
+        preview.load(path.clone(), &highlighter, &palette);
+        assert_eq!(preview.lines[0].raw_text(), "changed");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::mpsc::{channel, Receiver, Sender, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+const _TAB: &str = "    ";
+// Time to wait for the output after the child exits. A grandchild may keep the
+// pipes open for much longer.
+const _DRAIN_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// A line written by a child process, without the line ending.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputLine {
+    Stdout(String),
+    Stderr(String),
+}
+
+/// A child process whose stdout and stderr are captured line by line.
+pub struct CapturedChild {
+    child: Child,
+    rx: Receiver<OutputLine>,
+    closed: bool,  // True if both stdout and stderr are closed.
+    exited: Option<(ExitStatus, Instant)>,  // Exit status and the time it was found.
+}
+
+impl CapturedChild {
+    /// Spawn the command with piped stdout and stderr. If `input` is given, it is
+    /// written to the stdin of the child.
+    pub fn spawn(mut cmd: Command, input: Option<String>) -> std::io::Result<Self> {
+        let stdin = if input.is_some() { Stdio::piped() } else { Stdio::null() };
+        let mut child = cmd
+            .stdin(stdin)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+            // write in a thread not to block while the child fills its stdout
+            thread::spawn(move || stdin.write_all(input.as_bytes()));
+        }
+        let (tx, rx) = channel();
+        if let Some(stdout) = child.stdout.take() {
+            forward_lines(stdout, tx.clone(), OutputLine::Stdout);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            forward_lines(stderr, tx, OutputLine::Stderr);
+        }
+        Ok(Self { child, rx, closed: false, exited: None })
+    }
+
+    /// Get the lines written since the last call.
+    pub fn read_lines(&mut self) -> Vec<OutputLine> {
+        let mut lines = Vec::new();
+        loop {
+            match self.rx.try_recv() {
+                Ok(line) => lines.push(line),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+        lines
+    }
+
+    /// Get the exit status if the child has exited and all its output was read,
+    /// or if the output is still open a while after the child exited.
+    pub fn try_finish(&mut self) -> std::io::Result<Option<ExitStatus>> {
+        if self.exited.is_none() {
+            self.exited = self.child.try_wait()?.map(|status| (status, Instant::now()));
+        }
+        match self.exited {
+            Some((status, at)) if self.closed || at.elapsed() >= _DRAIN_TIMEOUT => Ok(Some(status)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Kill the child process.
+    pub fn kill(&mut self) -> std::io::Result<()> {
+        self.child.kill()
+    }
+}
+
+/// Read lines from `reader` in a thread and send them through `tx`.
+fn forward_lines<R: Read + Send + 'static>(
+    reader: R, tx: Sender<OutputLine>, wrap: fn(String) -> OutputLine
+) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    let line = String::from_utf8_lossy(&buf).trim_end_matches(['\n', '\r']).to_string();
+                    if tx.send(wrap(line)).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Remove line endings, escape sequences and control characters, and expand tabs
+/// so that the line is rendered correctly in the TUI.
+pub fn sanitize_line(line: &str) -> String {
+    let mut out = String::new();
+    let mut chars = line.trim_end_matches(['\n', '\r']).chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => {
+                // skip CSI sequences such as "\x1b[31m"
+                if chars.peek() == Some(&'[') {
+                    chars.next();
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+            }
+            '\t' => out.push_str(_TAB),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_line() {
+        assert_eq!(sanitize_line("abc\r\n"), "abc");
+        assert_eq!(sanitize_line("\x1b[1;31merror\x1b[0m: x"), "error: x");
+        assert_eq!(sanitize_line("a\tb\x07"), "a    b");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_captured_child() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo out; echo err 1>&2; exit 3"]);
+        let mut child = CapturedChild::spawn(cmd, None).unwrap();
+        let mut lines = Vec::new();
+        let status = loop {
+            lines.extend(child.read_lines());
+            if let Some(status) = child.try_finish().unwrap() {
+                break status;
+            }
+            thread::sleep(std::time::Duration::from_millis(10));
+        };
+        assert_eq!(status.code(), Some(3));
+        assert!(lines.contains(&OutputLine::Stdout("out".to_string())));
+        assert!(lines.contains(&OutputLine::Stderr("err".to_string())));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_captured_child_grandchild() {
+        // the grandchild keeps stdout open after the child exits
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "sleep 10 & echo out"]);
+        let mut child = CapturedChild::spawn(cmd, None).unwrap();
+        let start = Instant::now();
+        let mut lines = Vec::new();
+        let status = loop {
+            lines.extend(child.read_lines());
+            if let Some(status) = child.try_finish().unwrap() {
+                break status;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert!(status.success());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        lines.extend(child.read_lines());
+        assert_eq!(lines, vec![OutputLine::Stdout("out".to_string())]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_captured_child_input() {
+        let mut child = CapturedChild::spawn(Command::new("cat"), Some("a\nb\n".to_string())).unwrap();
+        let mut lines = Vec::new();
+        while child.try_finish().unwrap().is_none() {
+            lines.extend(child.read_lines());
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        lines.extend(child.read_lines());
+        assert_eq!(lines, vec![OutputLine::Stdout("a".to_string()), OutputLine::Stdout("b".to_string())]);
+    }
+}
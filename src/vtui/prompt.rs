@@ -0,0 +1,151 @@
+use super::{
+    rich::{RichText, RichLine},
+    theme::{Palette, parse_style},
+};
+
+/// Values that can be shown in the prompt.
+///
+/// The prompt template may contain the following placeholders.
+/// - `{tree}`: name of the tree.
+/// - `{path}`: current path. `{path:N}` shows only the last N parts.
+/// - `{dirty}`: "*" if the tree has unsaved changes. `{dirty:MARK}` uses MARK instead.
+/// - `{status}`: exit status of the last command.
+/// - `{count}`: number of items in the current directory.
+///
+/// Each placeholder can be styled as `{path:2|bold,cyan}`. `{{` and `}}` are the
+/// literal braces.
+pub struct PromptState {
+    pub tree: String,
+    pub path: Vec<String>,
+    pub modified: bool,
+    pub status: i32,
+    pub count: usize,
+}
+
+impl PromptState {
+    /// Get the text of a placeholder, or None if the name is unknown.
+    fn value(&self, name: &str, arg: Option<&str>) -> Option<String> {
+        let value = match name {
+            "tree" => self.tree.clone(),
+            "path" => {
+                let n = arg.and_then(|arg| arg.parse::<usize>().ok()).unwrap_or(self.path.len());
+                if self.path.len() > n {
+                    format!("…/{}", self.path[self.path.len() - n..].join("/"))
+                } else {
+                    self.path.join("/")
+                }
+            }
+            "dirty" => {
+                if self.modified { arg.unwrap_or("*").to_string() } else { String::new() }
+            }
+            "status" => self.status.to_string(),
+            "count" => self.count.to_string(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Render the prompt template.
+    pub fn render(&self, template: &str, palette: &Palette) -> RichLine {
+        let literal = palette.prompt;
+        let mut line = RichLine::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut spec = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        spec.push(c);
+                    }
+                    match self.placeholder(&spec, palette).filter(|_| closed) {
+                        Some(segment) => {
+                            if !text.is_empty() {
+                                line.push(RichText::styled(std::mem::take(&mut text), literal));
+                            }
+                            line.push(segment);
+                        }
+                        None => {
+                            // keep unknown placeholders as they are
+                            text.push('{');
+                            text.push_str(&spec);
+                            if closed {
+                                text.push('}');
+                            }
+                        }
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            line.push(RichText::styled(text, literal));
+        }
+        line
+    }
+
+    /// Render a placeholder such as "path:2|bold,cyan".
+    fn placeholder(&self, spec: &str, palette: &Palette) -> Option<RichText> {
+        let (name, style) = match spec.split_once('|') {
+            Some((name, style)) => (name, parse_style(style)?),
+            None => (spec, palette.prompt),
+        };
+        let (name, arg) = match name.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (name, None),
+        };
+        let value = self.value(name.trim(), arg)?;
+        Some(RichText::styled(value, style))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tui::style::Color;
+
+    fn make_state() -> PromptState {
+        PromptState {
+            tree: "data".to_string(),
+            path: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            modified: true,
+            status: 1,
+            count: 4,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let state = make_state();
+        let palette = Palette::default();
+        let line = state.render("/[{tree}]/{path} > ", &palette);
+        assert_eq!(line.raw_text(), "/[data]/a/b/c > ");
+        let line = state.render("{path:2}{dirty} ({count}) [{status}] {{x}} {unknown}$ ", &palette);
+        assert_eq!(line.raw_text(), "…/b/c* (4) [1] {x} {unknown}$ ");
+        let line = state.render("{dirty:+}{path:5}", &palette);
+        assert_eq!(line.raw_text(), "+a/b/c");
+    }
+
+    #[test]
+    fn test_render_styled() {
+        let state = make_state();
+        let line = state.render("{tree|bold,cyan} > ", &Palette::default());
+        let spans = line.as_spans();
+        assert_eq!(spans.0[0].content, "data");
+        assert_eq!(spans.0[0].style.fg, Some(Color::Cyan));
+        assert_eq!(spans.0[1].style.fg, Some(Color::White));
+    }
+}
@@ -2,31 +2,58 @@ use tui::{
     style::{Color, Style, Modifier},
     text::{Span, Spans},
 };
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 #[derive(Debug, Clone)]
 pub struct RichText {
     text: String,
     style: Style,
+    link: Option<String>,  // The virtual path that this text refers to.
 }
 
 impl RichText {
     pub fn new(text: String, color: Color) -> Self {
         let style = Style::default().fg(color);
-        Self { text, style }
+        Self { text, style, link: None }
+    }
+
+    /// Create a new text with a fully specified style.
+    pub fn styled(text: String, style: Style) -> Self {
+        Self { text, style, link: None }
+    }
+
+    /// Link the text to a virtual path.
+    pub fn with_link(mut self, link: String) -> Self {
+        self.link = Some(link);
+        self
+    }
+
+    pub fn link(&self) -> Option<&String> {
+        self.link.as_ref()
     }
 
     pub fn as_span(&self) -> Span<'static> {
         Span::styled(self.text.clone(), self.style)
     }
 
+    /// Number of grapheme clusters in the text.
     pub fn len(&self) -> usize {
-        self.text.len()
+        grapheme_count(&self.text)
     }
 
-    /// Get subset of the text with same style
+    /// Display width of the text.
+    pub fn width(&self) -> usize {
+        display_width(&self.text)
+    }
+
+    /// Get subset of the text with same style. `start` and `end` are grapheme
+    /// indices.
     pub fn subtext(&self, start: usize, end: usize) -> Self {
+        let start = grapheme_offset(&self.text, start);
+        let end = grapheme_offset(&self.text, end);
         let text = self.text[start..end].to_string();
-        Self { text, style: self.style }
+        Self { text, style: self.style, link: self.link.clone() }
     }
 
     pub fn restyled(&self, style: Style) -> Self {
@@ -43,22 +70,22 @@ impl RichText {
         } else {
             new_style.add_modifier(style.add_modifier)
         };
-        Self { text: self.text.clone(), style: new_style }
+        Self { text: self.text.clone(), style: new_style, link: self.link.clone() }
     }
 
     fn split_at(&self, pos: usize) -> (Self, Self) {
-        let (left, right) = self.text.split_at(pos);
-        let left = Self { text: left.to_string(), style: self.style };
-        let right = Self { text: right.to_string(), style: self.style };
+        let (left, right) = self.text.split_at(grapheme_offset(&self.text, pos));
+        let left = Self { text: left.to_string(), style: self.style, link: self.link.clone() };
+        let right = Self { text: right.to_string(), style: self.style, link: self.link.clone() };
         (left, right)
     }
 
     fn split3_at(&self, pos0: usize, pos1: usize) -> (Self, Self, Self) {
-        let (left, mid_right) = self.text.split_at(pos0);
-        let (middle, right) = mid_right.split_at(pos1 - pos0);
-        let left = Self { text: left.to_string(), style: self.style };
-        let middle = Self { text: middle.to_string(), style: self.style };
-        let right = Self { text: right.to_string(), style: self.style };
+        let (left, mid_right) = self.text.split_at(grapheme_offset(&self.text, pos0));
+        let (middle, right) = mid_right.split_at(grapheme_offset(mid_right, pos1 - pos0));
+        let left = Self { text: left.to_string(), style: self.style, link: self.link.clone() };
+        let middle = Self { text: middle.to_string(), style: self.style, link: self.link.clone() };
+        let right = Self { text: right.to_string(), style: self.style, link: self.link.clone() };
         (left, middle, right)
     }
 }
@@ -90,6 +117,19 @@ impl RichLine {
         self.texts.iter()
     }
 
+    /// Get the text displayed at the column `col`.
+    pub fn text_at(&self, col: usize) -> Option<&RichText> {
+        let mut start = 0;
+        for text in &self.texts {
+            let end = start + text.width();
+            if start <= col && col < end {
+                return Some(text);
+            }
+            start = end;
+        }
+        None
+    }
+
     /// Return the unstyled String.
     pub fn raw_text(&self) -> String {
         let mut text = String::new();
@@ -109,7 +149,58 @@ impl RichLine {
         self.texts.extend(line.texts);
     }
 
-    /// Partially change the style of the texts.
+    /// Display width of the line.
+    pub fn width(&self) -> usize {
+        self.texts.iter().map(|text| text.width()).sum()
+    }
+
+    /// Wrap the line into rows with display width at most `width`. Styles of the
+    /// texts are kept across the wrap points.
+    pub fn wrap(&self, width: usize) -> Vec<RichLine> {
+        if width == 0 {
+            return vec![self.clone()];
+        }
+        let mut rows = vec![RichLine::new()];
+        let mut col = 0;
+        for text in &self.texts {
+            let mut rest = text.clone();
+            loop {
+                if col + rest.width() <= width {
+                    col += rest.width();
+                    rows.last_mut().unwrap().push(rest);
+                    break;
+                }
+                // count the graphemes that fit in the rest of the row
+                let mut nfit = 0;
+                for grapheme in rest.text.graphemes(true) {
+                    col += display_width(grapheme);
+                    if col > width {
+                        break;
+                    }
+                    nfit += 1;
+                }
+                if nfit == 0 && rows.last().unwrap().texts.is_empty() {
+                    // a grapheme wider than the row
+                    nfit = 1;
+                }
+                let (left, right) = rest.split_at(nfit);
+                if left.len() > 0 {
+                    rows.last_mut().unwrap().push(left);
+                }
+                if right.len() == 0 {
+                    col = width;
+                    break;
+                }
+                rows.push(RichLine::new());
+                col = 0;
+                rest = right;
+            }
+        }
+        rows
+    }
+
+    /// Partially change the style of the texts. `start` and `end` are grapheme
+    /// indices.
     pub fn restyled(&self, start: usize, end: usize, style: Style) -> Self {
         let mut pos = 0;
         let mut sl = -1..-1;
@@ -188,6 +279,22 @@ impl From<Vec<String>> for RichLine {
     }
 }
 
+/// Number of grapheme clusters in a string.
+pub fn grapheme_count(s: &str) -> usize {
+    s.graphemes(true).count()
+}
+
+/// Byte offset of the `idx`-th grapheme cluster in a string. If `idx` is out of
+/// range, the length of the string is returned.
+pub fn grapheme_offset(s: &str, idx: usize) -> usize {
+    s.grapheme_indices(true).nth(idx).map(|(pos, _)| pos).unwrap_or(s.len())
+}
+
+/// Width of a string when it is displayed in a terminal.
+pub fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -246,6 +353,64 @@ mod test {
         assert_eq!(line.texts[0].style.fg, Some(Color::White));
     }
 
+    #[test]
+    fn test_rich_line_text_at() {
+        let mut line = RichLine::new();
+        line.push(RichText::new("ls: ".to_string(), Color::White));
+        line.push(RichText::new("dir".to_string(), Color::White).with_link("~/dir".to_string()));
+        assert_eq!(line.text_at(0).unwrap().link(), None);
+        assert_eq!(line.text_at(4).unwrap().link(), Some(&"~/dir".to_string()));
+        assert_eq!(line.text_at(6).unwrap().link(), Some(&"~/dir".to_string()));
+        assert!(line.text_at(7).is_none());
+    }
+
+    #[test]
+    fn test_rich_line_wrap() {
+        let mut line = RichLine::new();
+        line.push(RichText::new("abc".to_string(), Color::White));
+        line.push(RichText::new("defgh".to_string(), Color::Red));
+        let rows = line.wrap(4);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].raw_text(), "abcd");
+        assert_eq!(rows[1].raw_text(), "efgh");
+        assert_eq!(rows[0].texts[1].style.fg, Some(Color::Red));
+        assert_eq!(rows[1].texts[0].style.fg, Some(Color::Red));
+
+        let rows = line.wrap(3);
+        assert_eq!(rows.iter().map(|r| r.raw_text()).collect::<Vec<_>>(), vec!["abc", "def", "gh"]);
+        assert_eq!(RichLine::new().wrap(3).len(), 1);
+    }
+
+    #[test]
+    fn test_rich_text_unicode() {
+        let text = RichText::new("日本語.txt".to_string(), Color::White);
+        assert_eq!(text.len(), 7);
+        assert_eq!(text.width(), 10);
+        let (left, right) = text.split_at(3);
+        assert_eq!(left.text, "日本語");
+        assert_eq!(right.text, ".txt");
+        let (_, middle, _) = RichText::new("cafe\u{301}!".to_string(), Color::White).split3_at(3, 4);
+        assert_eq!(middle.text, "e\u{301}");
+    }
+
+    #[test]
+    fn test_rich_line_restyled_unicode() {
+        let line = RichLine::from(vec!["cd ".to_string(), "日本".to_string()]);
+        let line = line.restyled(4, 5, Style::default().fg(Color::Red));
+        assert_eq!(line.raw_text(), "cd 日本");
+        assert_eq!(line.texts.last().unwrap().text, "");
+        assert_eq!(line.texts[line.texts.len() - 2].text, "本");
+    }
+
+    #[test]
+    fn test_rich_line_wrap_wide() {
+        let line = RichLine::from(vec!["a日本語".to_string()]);
+        let rows = line.wrap(4);
+        assert_eq!(rows.iter().map(|r| r.raw_text()).collect::<Vec<_>>(), vec!["a日", "本語"]);
+        let rows = RichLine::from(vec!["日本".to_string()]).wrap(1);
+        assert_eq!(rows.iter().map(|r| r.raw_text()).collect::<Vec<_>>(), vec!["日", "本"]);
+    }
+
     #[test]
     fn test_rich_line_restyled_raw_text() {
         let line = RichLine::from(vec!["Hello ".to_string(), "World".to_string()]);
@@ -0,0 +1,144 @@
+use tui::style::Style;
+use super::{
+    history::History,
+    rich::{RichLine, grapheme_count},
+};
+
+/// State of the search in the scrollback.
+pub struct Search {
+    pub query: String,
+    pub current: Option<usize>,  // Index of the line of the current match.
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Self { query: String::new(), current: None }
+    }
+
+    fn matches(&self, line: &RichLine) -> bool {
+        !self.query.is_empty() && line.raw_text().contains(&self.query)
+    }
+
+    /// Find the last matching line before the line at `before`.
+    pub fn find_prev(&self, lines: &History<RichLine>, before: usize) -> Option<usize> {
+        (0..before.min(lines.len())).rev().find(|&idx| self.matches(&lines[idx]))
+    }
+
+    /// Find the first matching line after the line at `after`.
+    pub fn find_next(&self, lines: &History<RichLine>, after: usize) -> Option<usize> {
+        (after + 1..lines.len()).find(|&idx| self.matches(&lines[idx]))
+    }
+
+    /// Highlight all the matches in a line with `style`.
+    pub fn highlight(&self, line: &RichLine, style: Style) -> RichLine {
+        let mut line = line.clone();
+        if self.query.is_empty() {
+            return line;
+        }
+        let raw = line.raw_text();
+        for (idx, matched) in raw.match_indices(&self.query) {
+            let start = grapheme_count(&raw[..idx]);
+            line = line.restyled(start, start + grapheme_count(matched), style);
+        }
+        line
+    }
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lines selected in the scrollback.
+pub struct LineSelection {
+    pub anchor: usize,
+    pub pos: usize,
+}
+
+impl LineSelection {
+    pub fn new(pos: usize) -> Self {
+        Self { anchor: pos, pos }
+    }
+
+    /// Move the selection to `pos`. If `keep_selection` is true, the selection is
+    /// extended instead.
+    pub fn move_to(&mut self, pos: usize, keep_selection: bool) {
+        self.pos = pos;
+        if !keep_selection {
+            self.anchor = pos;
+        }
+    }
+
+    /// Canonicalize the selection, so that start <= end
+    pub fn range(&self) -> std::ops::RangeInclusive<usize> {
+        if self.anchor <= self.pos {
+            self.anchor..=self.pos
+        } else {
+            self.pos..=self.anchor
+        }
+    }
+
+    pub fn contains(&self, idx: usize) -> bool {
+        self.range().contains(&idx)
+    }
+
+    /// Get the selected lines as a string.
+    pub fn text(&self, lines: &History<RichLine>) -> String {
+        self.range()
+            .filter(|&idx| idx < lines.len())
+            .map(|idx| lines[idx].raw_text())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tui::style::Color;
+
+    fn make_lines(texts: &[&str]) -> History<RichLine> {
+        let mut lines = History::new(100);
+        for text in texts {
+            lines.add(RichLine::from(vec![text.to_string()]));
+        }
+        lines
+    }
+
+    #[test]
+    fn test_search() {
+        let lines = make_lines(&["foo", "bar", "foobar", "baz"]);
+        let mut search = Search::new();
+        assert_eq!(search.find_prev(&lines, lines.len()), None);
+        search.query = "foo".to_string();
+        assert_eq!(search.find_prev(&lines, lines.len()), Some(2));
+        assert_eq!(search.find_prev(&lines, 2), Some(0));
+        assert_eq!(search.find_prev(&lines, 0), None);
+        assert_eq!(search.find_next(&lines, 0), Some(2));
+        assert_eq!(search.find_next(&lines, 2), None);
+    }
+
+    #[test]
+    fn test_search_highlight() {
+        let mut search = Search::new();
+        search.query = "o".to_string();
+        let line = RichLine::from(vec!["foo ".to_string(), "bar".to_string()]);
+        let highlighted = search.highlight(&line, Style::default().bg(Color::Yellow));
+        assert_eq!(highlighted.raw_text(), "foo bar");
+        let spans = highlighted.as_spans();
+        let nmatched = spans.0.iter().filter(|span| span.style.bg == Some(Color::Yellow)).count();
+        assert_eq!(nmatched, 2);
+    }
+
+    #[test]
+    fn test_line_selection() {
+        let lines = make_lines(&["a", "b", "c"]);
+        let mut selection = LineSelection::new(2);
+        selection.move_to(1, true);
+        assert_eq!(selection.range(), 1..=2);
+        assert_eq!(selection.text(&lines), "b\nc");
+        selection.move_to(0, false);
+        assert_eq!(selection.text(&lines), "a");
+    }
+}
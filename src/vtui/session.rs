@@ -9,192 +9,594 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use std::path::PathBuf;
+use std::process::Command;
+use structopt::StructOpt;
 
-use super::super::terminal::VCommand;
-use super::super::tree;
-use super::super::{get_json_path, get_vtree_path, get_relative_vtree_path};
+use super::super::config::Config;
+use super::super::terminal::{VCommand, Pipeline, parse_string, script_lines};
+use super::super::tree::{self, error::TreeError, core::shell_quote};
+use super::super::{
+    get_json_path, get_virtual_files_path, load_tree, files_used_by_others, is_virtual_file,
+};
 use super::{
-    vtui::process_keys, 
-    app::App,
+    vtui::{process_keys, run_captured, run_jobs, wait_captured},
+    app::{App, virtual_file_candidate},
+    jobs::parse_job_spec,
 };
 
-const _VIRTUAL_FILES: &str = "virtual-files";
-
+/// Enter the virtual terminal of the tree `name`.
 pub fn enter(name: String) -> std::io::Result<()> {
+    run(name, false)
+}
+
+/// Enter the tree browser of the tree `name`.
+pub fn browse(name: String) -> std::io::Result<()> {
+    run(name, true)
+}
+
+fn run(name: String, browse: bool) -> std::io::Result<()> {
+    let root = get_json_path(&name)?;
+    let tree = load_tree(&name)?;
+    let mut app = App::with_config(tree, Config::load()?)?;
+    app.browser.active = browse;
+
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let root = get_json_path(&name)?;
-    if !root.exists() {
-        return Err(
-            std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                format!("Virtual directory {} does not exist.", name),
-            )
-        )?;
-    }
-    let tree = tree::TreeModel::from_file(&root)?;
-    let mut app = App::new(tree);
-
     loop {
         // get valid input
         let user_input = process_keys(&mut terminal, &mut app)?;
 
-        let input = match VCommand::from_string(&user_input){
-            Ok(input) => input,
+        let pipeline = match Pipeline::from_string(&user_input, &app.config.aliases){
+            Ok(pipeline) => pipeline,
             Err(e) => {
+                app.last_status = 2;
                 app.print_error(e);
                 continue;
             }
         };
-        let output = match input {
-            VCommand::Empty => {
-                Ok(())
+        app.last_status = 0;
+        if let (Some(VCommand::Exit { discard }), 1, None) =
+            (pipeline.commands.first(), pipeline.commands.len(), &pipeline.redirect)
+        {
+            if !discard {
+                app.tree.to_file(root.as_path())?;
             }
-            VCommand::Cd { name } => {
-                match name {
-                    Some(path) => app.tree.move_by_string(&path),
-                    None => Ok(app.tree.move_to_home()),
-                }
-            
+            app.jobs.kill_all();
+            break;
+        }
+        match run_pipeline(&mut terminal, &mut app, pipeline)? {
+            Ok(_) => {}
+            Err(err) => {
+                app.last_status = 1;
+                app.print_error(err);
             }
-            VCommand::Tree { name } => {
-                match name {
-                    Some(name) => {
-                        match app.tree.get_item(&name) {
-                            Ok(item) => {
-                                app.print_text(format!("{}", item))
-                            }
-                            Err(e) => {
-                                app.print_error(e);
-                            }
-                        };
-                    }
-                    None => {
-                        match app.tree.current_item() {
-                            Ok(item) => {
-                                app.print_text(format!("{}", item))
-                            }
-                            Err(e) => {
-                                app.print_error(e);
-                            }
-                        };
-                    }
+        }
+    }
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal.show_cursor()?;
+
+    Ok(())
+}
+
+/// Run the commands of a pipeline. The output of each command is written to the
+/// stdin of the next one, and the output of the last one is written to the file
+/// of the redirection if any.
+fn run_pipeline(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App, pipeline: Pipeline
+) -> std::io::Result<tree::error::Result<()>> {
+    if pipeline.background {
+        return Ok(start_job(app, pipeline));
+    }
+    let last = pipeline.commands.len() - 1;
+    let mut input = None;
+    for (idx, command) in pipeline.commands.into_iter().enumerate() {
+        let stdin = input.take();
+        let output = if idx < last || pipeline.redirect.is_some() {
+            let (output, text) = app.capture_output(|app| run_command(terminal, app, command, stdin));
+            input = Some(text);
+            output
+        } else {
+            // the output is shown, or captured by the caller such as `source`
+            run_command(terminal, app, command, stdin)
+        };
+        if let Err(err) = output? {
+            return Ok(Err(err));
+        }
+    }
+    match (pipeline.redirect, input) {
+        (Some(redirect), Some(text)) => {
+            let candidate = virtual_file_candidate(&redirect.target)?;
+            Ok(app.tree.write_file(&redirect.target, &text, redirect.append, candidate))
+        }
+        _ => Ok(Ok(())),
+    }
+}
+
+/// Run a virtual command. `input` is the output of the previous command in the
+/// pipeline, which is passed to the stdin of `call`.
+fn run_command(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut App,
+    command: VCommand,
+    input: Option<String>,
+) -> std::io::Result<tree::error::Result<()>> {
+    let output = match command {
+        VCommand::Empty => {
+            Ok(())
+        }
+        VCommand::Cd { name } => {
+            match name {
+                Some(path) => app.tree.move_by_string(&path),
+                None => Ok(app.tree.move_to_home()),
+            }
+        
+        }
+        VCommand::Tree { name } => {
+            let name = name.unwrap_or(".".to_string());
+            match app.tree.get_item(&name) {
+                Ok(item) => {
+                    let base = app.tree.resolve_virtual_path(&name);
+                    let lines = item
+                        .tree_lines()
+                        .into_iter()
+                        .map(|(line, relpath)| {
+                            let name = relpath.last().unwrap_or(&item.name).clone();
+                            (line, name, [base.clone(), relpath].concat())
+                        })
+                        .collect();
+                    app.print_linked(lines);
+                    Ok(())
                 }
-                Ok(())
+                Err(e) => Err(e),
             }
-            VCommand::Ls { name, desc } => {
-                let str = if desc {
-                    app.tree.ls_detailed(name)
-                } else {
-                    app.tree.ls_simple(name)
-                };
-                match str {
-                    Ok(s) => {
-                        app.print_text(s);
-                        Ok(())
-                    }
-                    Err(e) => {
-                        Err(e)
+        }
+        VCommand::Ls { name, desc } => {
+            let dir = app.tree.resolve_virtual_path(&name.clone().unwrap_or(".".to_string()));
+            let str = if desc {
+                app.tree.ls_detailed(name)
+            } else {
+                app.tree.ls_simple(name)
+            };
+            match str {
+                Ok(s) => {
+                    let names = app.tree
+                        .get_item(&format!("~/{}", dir.join("/")))
+                        .map(|item| item.children_names())
+                        .unwrap_or_default();
+                    if desc {
+                        let lines = s
+                            .split('\n')
+                            .zip(names)
+                            .map(|(line, name)| {
+                                let path = [dir.clone(), vec![name.clone()]].concat();
+                                (line.to_string(), name, path)
+                            })
+                            .collect();
+                        app.print_linked(lines);
+                    } else {
+                        app.print_names(&dir, names);
                     }
+                    Ok(())
+                }
+                Err(e) => {
+                    Err(e)
                 }
             }
-            VCommand::Pwd => {
-                app.print_text(format!("./{}/{}", app.tree.root.name, app.tree.pwd()));
-                Ok(())
-            }
-            VCommand::Cat { name } => {
-                match app.tree.read_file(&name) {
-                    Ok(text) => app.print_text(text),
-                    Err(e) => app.print_error(e),
-                };
-                Ok(())
-            }
-            VCommand::Touch { name } => {
-                let vpath_cand = get_relative_vtree_path(true)?
-                    .join(_VIRTUAL_FILES)
-                    .join(name.clone());
-                // find unique file name
-                app.tree.create_new_file(&name, vpath_cand)
+        }
+        VCommand::Pwd => {
+            app.print_text(format!("./{}/{}", app.tree.root.name, app.tree.pwd()));
+            Ok(())
+        }
+        VCommand::Cat { name, plain, number } => {
+            app.tree.read_file(&name).map(|text| {
+                let path = app.tree.entity_abspath(&name).unwrap_or_default();
+                app.print_file(&text, &path, plain, number);
+                app.preview.last_target = Some(path);
+            })
+        }
+        VCommand::Touch { name } => {
+            // find unique file name
+            app.tree.create_new_file(&name, virtual_file_candidate(&name)?)
+        }
+        VCommand::Open { name } => {
+            app.tree.open_file(&name, app.config.opener.as_deref())
+        }
+        VCommand::Edit { name } => {
+            edit_file(terminal, app, name)
+        }
+        VCommand::Cp { src, dst } => {
+            app.tree.add_alias(dst.as_ref(), PathBuf::from(src))
+        }
+        VCommand::Desc { name, desc } => {
+            let name = name.unwrap_or(".".to_string());
+            match desc {
+                Some(desc) => app.tree.set_description(&name, Some(desc)),
+                None => {
+                    // TODO: enter description mode
+                    app.tree.get_item(&name).map(|_| ())
+                }
             }
-            VCommand::Open { name } => {
-                app.tree.open_file(&name)
+        }
+        VCommand::Call { interactive, shell, no_resolve, vec } => {
+            let options = app.call_options(shell, no_resolve);
+            let result = if interactive && (input.is_some() || app.capture.is_some()) {
+                Err(TreeError::new("call --interactive cannot be used in a pipeline.".to_string()))
+            } else if interactive {
+                suspend(terminal, || app.tree.call_command(&vec, &options))?
+            } else {
+                app.tree.command(&vec, &options).and_then(|cmd| {
+                    run_captured(terminal, app, cmd, input)
+                        .map_err(|err| TreeError::new(format!("Error calling command: {}", err)))
+                })
+            };
+            result.map(|code| app.last_status = code)
+        }
+        VCommand::Foreach { jobs, pattern, vec } => {
+            foreach(terminal, app, jobs, &pattern, vec)?
+        }
+        VCommand::Jobs => {
+            let lines: Vec<String> = app.jobs.iter().map(|job| job.describe()).collect();
+            for line in lines {
+                app.print_text(line);
             }
-            VCommand::Cp { src, dst } => {
-                app.tree.add_alias(dst.as_ref(), PathBuf::from(src))
+            Ok(())
+        }
+        VCommand::Fg { job } => {
+            foreground(terminal, app, job)?
+        }
+        VCommand::Kill { job: spec } => {
+            match parse_job_spec(&spec).and_then(|id| app.jobs.get_mut(id)) {
+                Some(job) if job.is_running() => {
+                    job.kill().map_err(|err| TreeError::new(format!("{}", err)))
+                }
+                Some(job) => Err(TreeError::new(format!("Job %{} has already finished.", job.id))),
+                None => Err(TreeError::new(format!("No such job: {}", spec))),
             }
-            VCommand::Desc { name, desc } => {
-                let item_result = match name {
-                    Some(name) => app.tree.get_item(&name),
-                    None => app.tree.get_item(&app.tree.pwd()),
-                };
-                let mut item = item_result.unwrap().clone();
-                let item = item.as_mut();
-                match desc {
-                    Some(desc) => {
-                        item.desc = Some(desc);
-                    }
-                    None => {
-                        // TODO: enter description mode
+        }
+        VCommand::Set { assignment } => {
+            match assignment {
+                Some(assignment) => app.set_variable(&assignment),
+                None => {
+                    let mut variables: Vec<_> = app.all_variables().into_iter().collect();
+                    variables.sort();
+                    for (name, value) in variables {
+                        app.print_text(format!("{}={}", name, value));
                     }
+                    Ok(())
                 }
-                Ok(())
-            }
-            VCommand::Call { vec } => {
-                terminal.show_cursor()?;
-                app.tree.call_command(&vec)
-            }
-            VCommand::Mkdir { name } => {
-                app.tree.make_directory(&name)
             }
-            VCommand::Rm { name } => {
-                match app.tree.get_item(&name) {
-                    Ok(item) => {
-                        match &item.entity {
-                            Some(path) => {
-                                let vfiles_path = get_vtree_path(true)?
-                                    .join(_VIRTUAL_FILES);
-                                if path.starts_with(vfiles_path) {
-                                    std::fs::remove_file(path)?;
-                                }
-                            }
-                            None => {}
-                        }
-                    }
-                    Err(err) => {
-                        app.print_error(err);
-                        continue;
-                    }
-                };
-                app.tree.remove_child(&name)
+        }
+        VCommand::Alias { definition } => {
+            define_alias(app, definition.join(" "))
+        }
+        VCommand::Unalias { name } => {
+            match app.config.aliases.remove(&name) {
+                Some(_) => Config::set_alias(&name, None)
+                    .map_err(|err| TreeError::new(format!("{}", err))),
+                None => Err(TreeError::new(format!("No such alias: {}", name))),
             }
-            VCommand::Mv { src, dst } => {
-                app.tree.move_child(&src, &dst)
+        }
+        VCommand::Mkdir { name } => {
+            app.tree.make_directory(&name)
+        }
+        VCommand::Rm { name } => {
+            remove_item(&mut app.tree, &name)
+        }
+        VCommand::Mv { src, dst } => {
+            app.tree.move_child(&src, &dst)
+        }
+        VCommand::SaveOutput { file } => {
+            let text = app.output_text() + "\n";
+            std::fs::write(&file, text)
+                .map_err(|err| TreeError::new(format!("{}: {}", file.display(), err)))
+        }
+        VCommand::Source { file } => {
+            source_file(terminal, app, &file)?
+        }
+        VCommand::Exit { .. } => {
+            Err(TreeError::new("exit cannot be used in a pipeline.".to_string()))
+        }
+    };
+    Ok(output)
+}
+
+/// Start a `call` command in the background.
+fn start_job(app: &mut App, pipeline: Pipeline) -> tree::error::Result<()> {
+    let mut commands = pipeline.commands.into_iter();
+    let (shell, no_resolve, vec) = match (commands.next(), commands.next()) {
+        (Some(VCommand::Call { interactive: false, shell, no_resolve, vec }), None) => {
+            (shell, no_resolve, vec)
+        }
+        _ => return Err(TreeError::new("Only a call command can run in the background.".to_string())),
+    };
+    let cmd = app.tree.command(&vec, &app.call_options(shell, no_resolve))?;
+    let name = format!("call {}{}", if shell { "--shell " } else { "" }, vec.join(" "));
+    let id = app.jobs
+        .spawn(name, cmd, pipeline.redirect)
+        .map_err(|err| TreeError::new(format!("Error calling command: {}", err)))?;
+    app.print_message(format!("[{}] started", id), app.palette.muted);
+    Ok(())
+}
+
+/// Print the output of a background job and wait for it in the foreground if it
+/// is still running. The job is removed from the job table.
+fn foreground(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App, spec: Option<String>
+) -> std::io::Result<tree::error::Result<()>> {
+    let id = match &spec {
+        Some(spec) => parse_job_spec(spec),
+        None => app.jobs.last_id(),
+    };
+    let mut job = match id.and_then(|id| app.jobs.remove(id)) {
+        Some(job) => job,
+        None => {
+            let msg = match spec {
+                Some(spec) => format!("No such job: {}", spec),
+                None => "No jobs.".to_string(),
+            };
+            return Ok(Err(TreeError::new(msg)));
+        }
+    };
+    // the stdout of a job with a redirection is written to the file
+    let redirect = job.redirect.take();
+    let mut text = job.stdout.take().unwrap_or_default();
+    for line in job.output.history.drain(..) {
+        app.print_output(line);
+    }
+    let code = match job.status {
+        Some(code) => {
+            let style = if code == 0 { app.palette.muted } else { app.palette.error };
+            app.print_message(format!("[exit status {}]", code), style);
+            code
+        }
+        None if redirect.is_some() => {
+            let (code, rest) = app.capture_output(|app| wait_captured(terminal, app, job.name, job.child));
+            text.push_str(&rest);
+            code?
+        }
+        None => wait_captured(terminal, app, job.name, job.child)?,
+    };
+    app.last_status = code;
+    match redirect {
+        Some(redirect) => {
+            let candidate = virtual_file_candidate(&redirect.target)?;
+            Ok(app.tree.write_file(&redirect.target, &text, redirect.append, candidate))
+        }
+        None => Ok(Ok(())),
+    }
+}
+
+/// Run each command in a script file. The script stops at the first command that
+/// fails, with the line number in the error message.
+fn source_file(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App, file: &PathBuf
+) -> std::io::Result<tree::error::Result<()>> {
+    let text = match std::fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(err) => return Ok(Err(TreeError::new(format!("{}: {}", file.display(), err)))),
+    };
+    let path = file.canonicalize()?;
+    if app.sourcing.contains(&path) {
+        return Ok(Err(TreeError::new(format!("{} is already being sourced.", file.display()))));
+    }
+    app.sourcing.push(path);
+    let mut output = Ok(());
+    for (lineno, line) in script_lines(&text) {
+        let result = match Pipeline::from_string(&line, &app.config.aliases) {
+            Ok(pipeline) if matches!(pipeline.commands[..], [VCommand::Exit { .. }]) => {
+                Err(TreeError::new("exit cannot be used in a script.".to_string()))
             }
-            VCommand::Exit { discard } => {
-                if !discard {
-                    app.tree.to_file(root.as_path())?;
+            Ok(pipeline) => run_pipeline(terminal, app, pipeline)?,
+            Err(err) => Err(TreeError::new(err.message.trim_start_matches("error: ").to_string())),
+        };
+        if let Err(err) = result {
+            output = Err(TreeError::new(format!("{}:{}: {}", file.display(), lineno, err)));
+            break;
+        }
+    }
+    app.sourcing.pop();
+    Ok(output)
+}
+
+/// Run `call` for each file matching `pattern`. `args` is the `call` command, in
+/// which `{}` is replaced by the entity path of each file. If there is no `{}`,
+/// the path is appended.
+fn foreach(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut App,
+    jobs: usize,
+    pattern: &str,
+    args: Vec<String>,
+) -> std::io::Result<tree::error::Result<()>> {
+    if args.first().map(|arg| arg.as_str()) != Some("call") {
+        return Ok(Err(TreeError::new("Expected `call` after the pattern.".to_string())));
+    }
+    let call = VCommand::from_iter_safe(["foreach".to_string()].into_iter().chain(args));
+    let (shell, no_resolve, vec) = match call {
+        Ok(VCommand::Call { interactive: false, shell, no_resolve, vec }) => (shell, no_resolve, vec),
+        Ok(_) => return Ok(Err(TreeError::new("foreach cannot run interactive commands.".to_string()))),
+        Err(err) => return Ok(Err(TreeError::new(err.message))),
+    };
+    let vpaths = match app.tree.glob(pattern) {
+        Ok(vpaths) if vpaths.is_empty() => {
+            return Ok(Err(TreeError::new(format!("No files match {}.", pattern))))
+        }
+        Ok(vpaths) => vpaths,
+        Err(err) => return Ok(Err(err)),
+    };
+    let options = app.call_options(shell, no_resolve);
+    let mut commands = Vec::new();
+    for vpath in vpaths {
+        let path = match app.tree.entity_abspath(&vpath) {
+            Ok(path) => path.to_string_lossy().to_string(),
+            Err(err) => return Ok(Err(err)),
+        };
+        let path = if shell { shell_quote(&path) } else { path };
+        let mut inputs: Vec<String> = vec.iter().map(|arg| arg.replace("{}", &path)).collect();
+        if !vec.iter().any(|arg| arg.contains("{}")) {
+            inputs.push(path);
+        }
+        match app.tree.command(&inputs, &options) {
+            Ok(cmd) => commands.push((vpath.trim_start_matches("~/").to_string(), cmd)),
+            Err(err) => return Ok(Err(err)),
+        }
+    }
+    let total = commands.len();
+    let results = run_jobs(terminal, app, commands, jobs)?;
+    let canceled = total - results.len();
+    let failed: Vec<String> = results
+        .into_iter()
+        .filter(|(_, code)| *code != 0)
+        .map(|(name, _)| name)
+        .collect();
+    let passed = total - canceled - failed.len();
+    let mut summary = format!("{} passed, {} failed", passed, failed.len());
+    if canceled > 0 {
+        summary.push_str(&format!(", {} canceled", canceled));
+    }
+    if passed == total {
+        app.print_message(summary, app.palette.success);
+    } else {
+        app.print_message(summary, app.palette.error);
+        if !failed.is_empty() {
+            app.print_message(format!("failed: {}", failed.join(", ")), app.palette.error);
+        }
+        app.last_status = 1;
+    }
+    Ok(Ok(()))
+}
+
+/// Define an alias by a definition such as "name=command", which is saved in the
+/// project config. If the definition is empty, all the aliases are printed, and
+/// if it is just a name, the alias of the name is printed.
+fn define_alias(app: &mut App, definition: String) -> tree::error::Result<()> {
+    let (name, command) = match definition.split_once('=') {
+        Some((name, command)) => (name.to_string(), command.trim().to_string()),
+        None => {
+            let mut aliases: Vec<_> = app.config.aliases.clone().into_iter().collect();
+            aliases.sort();
+            if !definition.is_empty() {
+                aliases.retain(|(name, _)| name == &definition);
+                if aliases.is_empty() {
+                    return Err(TreeError::new(format!("No such alias: {}", definition)));
                 }
-                break;
             }
-        };
-        match output {
-            Ok(_) => {}
-            Err(err) => {
-                app.print_error(err);
+            for (name, command) in aliases {
+                app.print_text(format!("{}='{}'", name, command));
             }
+            return Ok(());
         }
+    };
+    let valid = !name.is_empty()
+        && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        && name != "alias"
+        && name != "unalias";
+    if !valid {
+        return Err(TreeError::new(format!("Invalid alias name: {}", name)));
+    }
+    if command.is_empty() {
+        return Err(TreeError::new(format!("Alias {} is empty.", name)));
     }
+    Config::set_alias(&name, Some(&command)).map_err(|err| TreeError::new(format!("{}", err)))?;
+    app.config.aliases.insert(name, command);
+    Ok(())
+}
+
+/// Leave the TUI to run `f` in the normal terminal, and then restore the TUI.
+fn suspend<T>(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, f: impl FnOnce() -> T
+) -> std::io::Result<T> {
     disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
     terminal.show_cursor()?;
+    let out = f();
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture)?;
+    terminal.clear()?;  // force a full redraw
+    Ok(out)
+}
 
-    Ok(())
+/// The editor command given by $VISUAL or $EDITOR.
+fn editor_command() -> Vec<String> {
+    for var in ["VISUAL", "EDITOR"] {
+        if let Ok(value) = std::env::var(var) {
+            let args = parse_string(&value);
+            if !args.is_empty() {
+                return args;
+            }
+        }
+    }
+    if cfg!(target_os = "windows") {
+        vec!["notepad".to_string()]
+    } else {
+        vec!["vi".to_string()]
+    }
+}
+
+/// Edit the entity at `name` with the editor. If `name` is not given, the last
+/// file shown by `cat` is edited.
+fn edit_file(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App, name: Option<String>
+) -> tree::error::Result<()> {
+    let path = match name {
+        Some(name) => app.tree.entity_abspath(&name)?,
+        None => app.preview.last_target
+            .clone()
+            .ok_or_else(|| TreeError::new("No file to edit.".to_string()))?,
+    };
+    let mut args = editor_command();
+    let program = args.remove(0);
+    let status = suspend(terminal, || Command::new(&program).args(&args).arg(&path).status())
+        .map_err(|err| TreeError::new(format!("{}", err)))?;
+    app.preview.clear();  // the file may have been changed
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(TreeError::new(format!("{} exited with {}", program, status))),
+        Err(err) => Err(TreeError::new(format!("Error running {}: {}", program, err))),
+    }
+}
+
+/// Remove the item at `name` from the tree. If the item is backed by a virtual
+/// file, the file is also removed unless another item or another tree, such as a
+/// copy made by `vtree copy`, still uses it.
+pub fn remove_item(tree: &mut tree::TreeModel, name: &String) -> tree::error::Result<()> {
+    let item = tree.get_item(name)?;
+    if let Some(path) = item.entity.clone() {
+        let vfiles = get_virtual_files_path().map_err(|err| TreeError::new(format!("{}", err)))?;
+        if is_virtual_file(&path, &vfiles) && !is_shared(tree, &path)? {
+            std::fs::remove_file(&path)
+                .map_err(|err| TreeError::new(format!("{}: {}", path.display(), err)))?;
+        }
+    }
+    tree.remove_child(name)
+}
+
+/// True if the file at `path` is the entity of more than one item of `tree`, or
+/// of an item of another tree.
+fn is_shared(tree: &tree::TreeModel, path: &std::path::Path) -> tree::error::Result<bool> {
+    let path = match path.canonicalize() {
+        Ok(path) => path,
+        Err(_) => return Ok(false),
+    };
+    let count = tree.root
+        .entities()
+        .iter()
+        .filter(|item| item.entity.as_ref().and_then(|file| file.canonicalize().ok()) == Some(path.clone()))
+        .count();
+    if count > 1 {
+        return Ok(true);
+    }
+    let used = get_json_path(&tree.root.name)
+        .and_then(|json| files_used_by_others(&json))
+        .map_err(|err| TreeError::new(format!("{}", err)))?;
+    Ok(used.contains(&path))
 }
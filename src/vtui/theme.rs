@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use tui::style::{Color, Modifier, Style};
+
+use super::super::config::Config;
+
+/// Parse a color name such as "red", "light-blue" or "#ff8800".
+pub fn parse_color(name: &str) -> Option<Color> {
+    let name = name.trim().to_ascii_lowercase().replace('_', "-");
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        return Some(Color::Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8));
+    }
+    let color = match name.as_str() {
+        "reset" | "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "dark-gray" | "dark-grey" => Color::DarkGray,
+        "light-red" => Color::LightRed,
+        "light-green" => Color::LightGreen,
+        "light-yellow" => Color::LightYellow,
+        "light-blue" => Color::LightBlue,
+        "light-magenta" => Color::LightMagenta,
+        "light-cyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return None,
+    };
+    Some(color)
+}
+
+/// Parse a comma-separated style such as "bold,cyan" or "black,bg:yellow".
+pub fn parse_style(spec: &str) -> Option<Style> {
+    let mut style = Style::default();
+    for part in spec.split(',').map(|part| part.trim()).filter(|part| !part.is_empty()) {
+        style = match part.to_ascii_lowercase().as_str() {
+            "bold" => style.add_modifier(Modifier::BOLD),
+            "dim" => style.add_modifier(Modifier::DIM),
+            "italic" => style.add_modifier(Modifier::ITALIC),
+            "underlined" => style.add_modifier(Modifier::UNDERLINED),
+            "reversed" => style.add_modifier(Modifier::REVERSED),
+            lower => match lower.strip_prefix("bg:") {
+                Some(bg) => style.bg(parse_color(bg)?),
+                None => style.fg(parse_color(part)?),
+            },
+        };
+    }
+    Some(style)
+}
+
+// Built-in themes. Each entry is a key of the palette and its style. "syntax" is
+// the syntect theme used to highlight files, or empty to disable highlighting.
+const _DARK: &[(&str, &str)] = &[
+    ("text", "white"),
+    ("error", "red"),
+    ("command", "yellow"),
+    ("quoted", "blue"),
+    ("line-number", "dark-gray"),
+    ("prompt", "white"),
+    ("cursor", "black,bg:#6c6c6c"),
+    ("selection", "black,bg:#808080"),
+    ("border", "yellow"),
+    ("muted", "dark-gray"),
+    ("directory", "yellow"),
+    ("success", "green"),
+    ("match", "black,bg:yellow"),
+    ("current-match", "black,bg:light-red"),
+    ("highlight", "bg:#6c6c6c"),
+    ("syntax", "base16-ocean.dark"),
+];
+
+const _LIGHT: &[(&str, &str)] = &[
+    ("text", "black"),
+    ("error", "red"),
+    ("command", "magenta"),
+    ("quoted", "blue"),
+    ("line-number", "dark-gray"),
+    ("prompt", "black"),
+    ("cursor", "white,bg:#505050"),
+    ("selection", "black,bg:#c0c0c0"),
+    ("border", "blue"),
+    ("muted", "dark-gray"),
+    ("directory", "blue"),
+    ("success", "green"),
+    ("match", "black,bg:light-yellow"),
+    ("current-match", "black,bg:light-red"),
+    ("highlight", "bg:#d0d0d0"),
+    ("syntax", "InspiredGitHub"),
+];
+
+const _HIGH_CONTRAST: &[(&str, &str)] = &[
+    ("text", "white"),
+    ("error", "light-red,bold"),
+    ("command", "light-yellow,bold"),
+    ("quoted", "light-cyan"),
+    ("line-number", "white"),
+    ("prompt", "white,bold"),
+    ("cursor", "black,bg:white"),
+    ("selection", "black,bg:light-cyan"),
+    ("border", "white"),
+    ("muted", "white"),
+    ("directory", "light-yellow,bold"),
+    ("success", "light-green,bold"),
+    ("match", "black,bg:light-yellow"),
+    ("current-match", "black,bg:light-magenta"),
+    ("highlight", "black,bg:white"),
+    ("syntax", "base16-eighties.dark"),
+];
+
+const _NO_COLOR: &[(&str, &str)] = &[
+    ("text", ""),
+    ("error", ""),
+    ("command", ""),
+    ("quoted", ""),
+    ("line-number", ""),
+    ("prompt", ""),
+    ("cursor", "reversed"),
+    ("selection", "reversed"),
+    ("border", ""),
+    ("muted", ""),
+    ("directory", "bold"),
+    ("success", ""),
+    ("match", "underlined"),
+    ("current-match", "reversed"),
+    ("highlight", "reversed"),
+    ("syntax", ""),
+];
+
+// Custom themes may inherit each other up to this depth.
+const _MAX_THEME_DEPTH: usize = 8;
+
+/// Names of the built-in themes.
+pub const THEMES: &[&str] = &["dark", "light", "high-contrast", "no-color"];
+
+/// Styles used in the virtual terminal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub text: Style,  // Plain output.
+    pub error: Style,  // Error messages.
+    pub command: Style,  // Command name in the input line.
+    pub quoted: Style,  // Quoted arguments in the input line.
+    pub line_number: Style,  // Line numbers of `cat -n`.
+    pub prompt: Style,  // Literal text in the prompt.
+    pub cursor: Style,
+    pub selection: Style,  // Selected text in the input line.
+    pub border: Style,
+    pub muted: Style,  // Descriptions and help texts.
+    pub directory: Style,  // Directories in the tree browser.
+    pub success: Style,  // Success messages.
+    pub matched: Style,  // Search matches.
+    pub current_match: Style,  // The current search match.
+    pub highlight: Style,  // The selected row of the tree browser.
+    pub syntax: Option<String>,  // Syntect theme, or None if files are not highlighted.
+}
+
+fn invalid_data(msg: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+/// True if the NO_COLOR environment variable is set to a non-empty value.
+pub fn no_color_requested() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+/// Get the style specs of a built-in or a custom theme.
+fn theme_specs(name: &str, config: &Config, depth: usize) -> std::io::Result<HashMap<String, String>> {
+    let table = match name {
+        "dark" => _DARK,
+        "light" => _LIGHT,
+        "high-contrast" => _HIGH_CONTRAST,
+        "no-color" => _NO_COLOR,
+        _ => {
+            let custom = config.themes
+                .get(name)
+                .ok_or_else(|| invalid_data(format!("Unknown theme: {}", name)))?;
+            if depth >= _MAX_THEME_DEPTH {
+                return Err(invalid_data(format!("Theme {} inherits too deeply.", name)));
+            }
+            let base = custom.get("base").map(|s| s.as_str()).unwrap_or("dark");
+            let mut specs = theme_specs(base, config, depth + 1)?;
+            for (key, spec) in custom.iter().filter(|(key, _)| key.as_str() != "base") {
+                specs.insert(key.clone(), spec.clone());
+            }
+            return Ok(specs);
+        }
+    };
+    Ok(table.iter().map(|(key, spec)| (key.to_string(), spec.to_string())).collect())
+}
+
+impl Palette {
+    /// Build the palette from a theme and the color overrides in the config. If
+    /// `no_color` is true, such as when NO_COLOR is set, the no-color theme is
+    /// always used.
+    pub fn from_config(config: &Config, no_color: bool) -> std::io::Result<Self> {
+        if no_color {
+            return Self::from_specs(theme_specs("no-color", config, 0)?);
+        }
+        let mut specs = theme_specs(&config.theme, config, 0)?;
+        for (key, spec) in &config.colors {
+            specs.insert(key.clone(), spec.clone());
+        }
+        Self::from_specs(specs)
+    }
+
+    fn from_specs(mut specs: HashMap<String, String>) -> std::io::Result<Self> {
+        let syntax = specs.remove("syntax").filter(|name| !name.is_empty());
+        let mut take = |key: &str| {
+            let spec = specs.remove(key).unwrap_or_default();
+            parse_style(&spec).ok_or_else(|| invalid_data(format!("Invalid style for {}: {}", key, spec)))
+        };
+        let palette = Self {
+            text: take("text")?,
+            error: take("error")?,
+            command: take("command")?,
+            quoted: take("quoted")?,
+            line_number: take("line-number")?,
+            prompt: take("prompt")?,
+            cursor: take("cursor")?,
+            selection: take("selection")?,
+            border: take("border")?,
+            muted: take("muted")?,
+            directory: take("directory")?,
+            success: take("success")?,
+            matched: take("match")?,
+            current_match: take("current-match")?,
+            highlight: take("highlight")?,
+            syntax,
+        };
+        if let Some(key) = specs.keys().next() {
+            return Err(invalid_data(format!("Unknown color key: {}", key)));
+        }
+        Ok(palette)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::from_specs(theme_specs("dark", &Config::default(), 0).unwrap()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("red"), Some(Color::Red));
+        assert_eq!(parse_color("Light_Blue"), Some(Color::LightBlue));
+        assert_eq!(parse_color("#ff8800"), Some(Color::Rgb(255, 136, 0)));
+        assert_eq!(parse_color("#ff88"), None);
+        assert_eq!(parse_color("purple"), None);
+    }
+
+    #[test]
+    fn test_parse_style() {
+        let style = parse_style("bold, cyan,bg:#000000").unwrap();
+        assert_eq!(style.fg, Some(Color::Cyan));
+        assert_eq!(style.bg, Some(Color::Rgb(0, 0, 0)));
+        assert!(style.add_modifier.contains(Modifier::BOLD));
+        assert!(parse_style("bold,purple").is_none());
+        assert_eq!(parse_style(""), Some(Style::default()));
+    }
+
+    #[test]
+    fn test_builtin_themes() {
+        let config = Config::default();
+        for name in THEMES {
+            let palette = Palette::from_specs(theme_specs(name, &config, 0).unwrap()).unwrap();
+            assert_ne!(palette.cursor, Style::default());
+        }
+        let palette = Palette::from_specs(theme_specs("no-color", &config, 0).unwrap()).unwrap();
+        assert_eq!(palette.command.fg, None);
+        assert_eq!(palette.syntax, None);
+    }
+
+    #[test]
+    fn test_custom_theme() {
+        let mut config = Config::default();
+        let mut theme = HashMap::new();
+        theme.insert("base".to_string(), "light".to_string());
+        theme.insert("error".to_string(), "magenta".to_string());
+        config.themes.insert("mine".to_string(), theme);
+        config.theme = "mine".to_string();
+        config.colors.insert("command".to_string(), "cyan,bold".to_string());
+
+        let palette = Palette::from_specs(theme_specs("mine", &config, 0).unwrap()).unwrap();
+        assert_eq!(palette.error.fg, Some(Color::Magenta));
+        assert_eq!(palette.text.fg, Some(Color::Black));
+        let palette = Palette::from_config(&config, false).unwrap();
+        assert_eq!(palette.command.fg, Some(Color::Cyan));
+        let palette = Palette::from_config(&config, true).unwrap();
+        assert_eq!(palette.command.fg, None);
+        assert_eq!(palette.error.fg, None);
+        config.colors.insert("unknown".to_string(), "red".to_string());
+        assert!(Palette::from_config(&config, false).is_err());
+        assert!(theme_specs("unknown-theme", &config, 0).is_err());
+
+        config.themes.get_mut("mine").unwrap().insert("base".to_string(), "mine".to_string());
+        assert!(theme_specs("mine", &config, 0).is_err());
+    }
+}
@@ -1,173 +1,440 @@
 use std::io::Write;
+use std::process::Command;
+use std::time::Duration;
 use tui::{
     backend::Backend,
+    layout::{Constraint, Direction, Layout},
     widgets::{Block, Borders, Paragraph},
-    style::{Color, Style},
     Terminal,
     Frame,
 };
 use crossterm::{
-    event::{self, Event, KeyEvent, KeyCode, KeyModifiers},
+    event::{self, Event, KeyEvent, KeyCode, KeyModifiers, MouseEvent, MouseEventKind, MouseButton},
 };
 
-use super::app::App;
-
-mod clipboard {
-    use arboard::Clipboard;
+use super::{
+    app::App,
+    browser::{process_browser_key, render_browser},
+    scrollback::{Search, LineSelection},
+    keymap::{Action, EditMode},
+    clipboard,
+    process::CapturedChild,
+};
 
-    pub fn get_text() -> String {
-        match Clipboard::new() {
-            Ok(mut clip) => clip.get_text().unwrap_or("".to_string()),
-            Err(_) => "".to_string()
-        }
-    }
-    
-    pub fn set_text(text: &String) {
-        match Clipboard::new() {
-            Ok(mut clip) => clip.set_text(text).unwrap_or(()),
-            Err(_) => {}
-        }
-    }
-}
 const _VIRTUAL_FILES: &str = "virtual-files";
 
+// Number of lines scrolled by a mouse wheel step.
+const _WHEEL_SCROLL: usize = 3;
+
+// The preview pane is hidden if the terminal is narrower than this.
+const _MIN_PREVIEW_WIDTH: u16 = 80;
+
+// Interval of polling the output of a running command.
+const _POLL_INTERVAL: Duration = Duration::from_millis(30);
+
 
 pub fn process_keys<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> std::io::Result<String> {
     let _ = std::io::stdout().flush();  // flush stdout
-    let prefix = app.tree.as_prefix();
-    app.print_text(prefix);
+    app.print_prompt();
     let output = loop {
-        terminal.draw(|f| render_ui(f, app))?;
-        if let Event::Key(KeyEvent {code, modifiers, ..}) = event::read()? {
-            match (code, modifiers) {
-                (KeyCode::Enter, KeyModifiers::NONE) => {
-                    let output = app.buffer.clone();
-                    app.buffer.push('\n');
-                    app.run_buffer();
-                    break output;
-                },
-                (KeyCode::Backspace, KeyModifiers::NONE) => { app.text_backspace_event(); },
-                (KeyCode::Delete, KeyModifiers::NONE) => { app.text_delete_event(); },
-                (KeyCode::Tab, KeyModifiers::NONE) => {
-                    app.run_completion();
-                },
-                (KeyCode::Esc, KeyModifiers::NONE) => {app.clear_buffer();},
-                (KeyCode::Left, KeyModifiers::NONE) => {
-                    app.text_move_cursor(-1, false) 
-                },
-                (KeyCode::Right, KeyModifiers::NONE) => { 
-                    app.text_move_cursor(1, false) 
-                },
-                (KeyCode::Left, KeyModifiers::CONTROL) => { 
-                    app.text_move_cursor_to_prev_word(false);
-                },
-                (KeyCode::Right, KeyModifiers::CONTROL) => {
-                    app.text_move_cursor_to_next_word(false)
-                },
-                (KeyCode::Left, KeyModifiers::SHIFT) => {
-                    app.text_move_cursor(-1, true)
-                },
-                (KeyCode::Right, KeyModifiers::SHIFT) => {
-                    app.text_move_cursor(1, true)
-                },
-                (KeyCode::Left, _) => {
-                    if modifiers.contains(KeyModifiers::CONTROL) && modifiers.contains(KeyModifiers::SHIFT) {
-                        app.text_move_cursor_to_prev_word(true);
-                    };
-                },
-                (KeyCode::Right, _) => {
-                    if modifiers.contains(KeyModifiers::CONTROL) && modifiers.contains(KeyModifiers::SHIFT) {
-                        app.text_move_cursor_to_next_word(true);
-                    };
-                },
-                (KeyCode::Home, KeyModifiers::NONE) => { app.cursor.move_to(0) },
-                (KeyCode::End, KeyModifiers::NONE) => { app.cursor.move_to(app.buffer.len()) },
-                (KeyCode::Home, KeyModifiers::SHIFT) => { app.cursor.select_to(0) },
-                (KeyCode::End, KeyModifiers::SHIFT) => { app.cursor.select_to(app.buffer.len()) },
-                (KeyCode::Char(c), KeyModifiers::NONE) => app.text_add_char(c),
-                (KeyCode::Char(c), KeyModifiers::SHIFT) => app.text_add_char(c),
-                (KeyCode::Char(c), KeyModifiers::CONTROL) => {
-                    match c {
-                        'a' => {
-                            app.cursor.move_to(0);
-                            app.cursor.select_to(app.buffer.len());
-                        },
-                        'c' => {
-                            let text = if app.cursor.selection_size() > 0 {
-                                app.text_selected()
-                            } else {
-                                app.buffer.clone()
-                            };
-                            clipboard::set_text(&text);
-                        },
-                        'v' => {
-                            app.insert_text(clipboard::get_text());
-                        },
-                        'w' => {
-                            // remove the previous word
-                            app.text_move_cursor_to_prev_word(true);
-                            app.text_backspace_event();
-                        }
-                        'x' => {
-                            if app.cursor.selection_size() > 0 {
-                                let text = app.text_selected();
-                                clipboard::set_text(&text);
-                                app.text_backspace_event();
-                            } else {
-                                let text = app.buffer.clone();
-                                clipboard::set_text(&text);
-                                app.clear_buffer();
-                            }
-                        },
-                        _ => {},
-                    }
-                },
-                // browse history
-                (KeyCode::Up, KeyModifiers::NONE) => {
-                    if let Some(buf) = app.history.prev() {
-                        app.set_buffer(buf);
-                    }
-                },
-                (KeyCode::Down, KeyModifiers::NONE) => {
-                    match app.history.next() {
-                        Some(buf) => app.set_buffer(buf),
-                        None => app.clear_buffer(),
-                    }
-                },
-                // scroll
-                (KeyCode::Up, KeyModifiers::SHIFT) => {
-                    if app.scroll_pos < app.lines.len() {
-                        app.scroll_pos += 1;
+        app.check_jobs();
+        if app.browser.active {
+            terminal.draw(|f| render_browser(f, app))?;
+        } else {
+            terminal.draw(|f| render_ui(f, app))?;
+        }
+        // poll the background jobs while waiting for an event
+        if app.jobs.has_running() && !event::poll(_POLL_INTERVAL)? {
+            continue;
+        }
+        let event = event::read()?;
+        if app.browser.active {
+            if let Event::Key(key) = event {
+                if let Some(output) = process_browser_key(app, key) {
+                    app.browser.toggle();
+                    app.set_buffer(output);
+                    break submit(app);
+                }
+            }
+            continue;
+        }
+        if let Event::Mouse(mouse) = event {
+            process_mouse(app, mouse);
+            continue;
+        }
+        if let Event::Key(KeyEvent {code, modifiers, ..}) = event {
+            if app.search.is_some() {
+                process_search_key(app, code, modifiers);
+                continue;
+            }
+            if app.line_selection.is_some() {
+                process_selection_key(app, code, modifiers);
+                continue;
+            }
+            let action = app.keymap.action(code, modifiers);
+            if !matches!(action, Some(Action::Yank) | Some(Action::YankPop)) {
+                app.last_yank = None;
+            }
+            match action {
+                Some(action) => {
+                    if let Some(output) = run_action(app, action) {
+                        break output;
                     }
-                },
-                (KeyCode::Down, KeyModifiers::SHIFT) => {
-                    if app.scroll_pos > 0 {
-                        app.scroll_pos -= 1;
+                }
+                None => {
+                    if let KeyCode::Char(c) = code {
+                        let no_mods = modifiers - KeyModifiers::SHIFT == KeyModifiers::NONE;
+                        if no_mods && app.keymap.inserts_text() {
+                            app.text_add_char(c);
+                        }
                     }
-                },
-                _ => {},
+                }
             }
         }
     };
     Ok(output)
 }
 
-fn render_ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
-    let rect = f.size();
+/// Run a command in the foreground, streaming its output into the scrollback until
+/// it exits. `input` is written to the stdin of the command. Ctrl-C kills the
+/// command. Returns the exit code.
+pub fn run_captured<B: Backend>(
+    terminal: &mut Terminal<B>, app: &mut App, cmd: Command, input: Option<String>
+) -> std::io::Result<i32> {
+    let name = cmd.get_program().to_string_lossy().to_string();
+    let child = CapturedChild::spawn(cmd, input)?;
+    wait_captured(terminal, app, name, child)
+}
+
+/// Wait for a child process, streaming its output into the scrollback. Ctrl-C
+/// kills the process. Returns the exit code.
+pub fn wait_captured<B: Backend>(
+    terminal: &mut Terminal<B>, app: &mut App, name: String, mut child: CapturedChild
+) -> std::io::Result<i32> {
+    app.running = Some(name);
+    let mut interrupted = false;
+    let status = loop {
+        for line in child.read_lines() {
+            app.print_output(line);
+        }
+        if let Some(status) = child.try_finish()? {
+            break status;
+        }
+        terminal.draw(|f| render_ui(f, app))?;
+        if !event::poll(_POLL_INTERVAL)? {
+            continue;
+        }
+        match event::read()? {
+            Event::Key(KeyEvent { code: KeyCode::Char('c'), modifiers: KeyModifiers::CONTROL, .. }) => {
+                // the child may have exited already
+                let _ = child.kill();
+                interrupted = true;
+            }
+            Event::Mouse(mouse) => process_mouse(app, mouse),
+            _ => {}
+        }
+    };
+    app.running = None;
+    let code = match status.code() {
+        Some(code) => code,
+        None if interrupted => 130,
+        None => 1,
+    };
+    if interrupted {
+        app.print_message("[interrupted]".to_string(), app.palette.error);
+    } else if code != 0 {
+        app.print_message(format!("[exit status {}]", code), app.palette.error);
+    } else {
+        app.print_message(format!("[exit status {}]", code), app.palette.muted);
+    }
+    Ok(code)
+}
+
+/// Run commands in the foreground, at most `max_jobs` at a time. The output of each
+/// command is streamed into the scrollback prefixed by its name. Ctrl-C kills the
+/// running commands and cancels the rest. Returns the names and the exit codes of
+/// the commands that have run.
+pub fn run_jobs<B: Backend>(
+    terminal: &mut Terminal<B>, app: &mut App, jobs: Vec<(String, Command)>, max_jobs: usize
+) -> std::io::Result<Vec<(String, i32)>> {
+    let total = jobs.len();
+    let mut pending = jobs.into_iter();
+    let mut running: Vec<(String, CapturedChild)> = Vec::new();
+    let mut results = Vec::new();
+    let mut interrupted = false;
+    loop {
+        while !interrupted && running.len() < max_jobs.max(1) {
+            let Some((name, cmd)) = pending.next() else { break };
+            match CapturedChild::spawn(cmd, None) {
+                Ok(child) => running.push((name, child)),
+                Err(err) => {
+                    app.print_message(format!("[{}] {}", name, err), app.palette.error);
+                    results.push((name, 127));
+                }
+            }
+        }
+        if running.is_empty() {
+            break;
+        }
+        app.running = Some(format!("{}/{} jobs done", results.len(), total));
+        let mut idx = 0;
+        while idx < running.len() {
+            let (name, child) = &mut running[idx];
+            for line in child.read_lines() {
+                app.print_prefixed(name, line);
+            }
+            match child.try_finish()? {
+                Some(status) => {
+                    let code = status.code().unwrap_or(if interrupted { 130 } else { 1 });
+                    let (name, _) = running.remove(idx);
+                    let style = if code == 0 { app.palette.muted } else { app.palette.error };
+                    app.print_message(format!("[{}] exit status {}", name, code), style);
+                    results.push((name, code));
+                }
+                None => idx += 1,
+            }
+        }
+        terminal.draw(|f| render_ui(f, app))?;
+        if !event::poll(_POLL_INTERVAL)? {
+            continue;
+        }
+        match event::read()? {
+            Event::Key(KeyEvent { code: KeyCode::Char('c'), modifiers: KeyModifiers::CONTROL, .. }) => {
+                for (_, child) in running.iter_mut() {
+                    let _ = child.kill();
+                }
+                interrupted = true;
+            }
+            Event::Mouse(mouse) => process_mouse(app, mouse),
+            _ => {}
+        }
+    }
+    app.running = None;
+    Ok(results)
+}
+
+/// Run the buffer and return it as the user input.
+fn submit(app: &mut App) -> String {
+    let output = app.buffer.clone();
+    app.buffer.push('\n');
+    app.run_buffer();
+    app.keymap.mode = EditMode::Insert;
+    output
+}
+
+/// Run an action bound to a key. Returns the user input if the action submits it.
+fn run_action(app: &mut App, action: Action) -> Option<String> {
+    match action {
+        Action::Submit => return Some(submit(app)),
+        Action::Complete => app.run_completion(),
+        Action::ClearBuffer => app.clear_buffer(),
+        Action::MoveLeft => app.text_move_cursor(-1, false),
+        Action::MoveRight => app.text_move_cursor(1, false),
+        Action::MoveWordLeft => app.text_move_cursor_to_prev_word(false),
+        Action::MoveWordRight => app.text_move_cursor_to_next_word(false),
+        Action::MoveHome => app.cursor.move_to(0),
+        Action::MoveEnd => app.cursor.move_to(app.buffer_len()),
+        Action::SelectLeft => app.text_move_cursor(-1, true),
+        Action::SelectRight => app.text_move_cursor(1, true),
+        Action::SelectWordLeft => app.text_move_cursor_to_prev_word(true),
+        Action::SelectWordRight => app.text_move_cursor_to_next_word(true),
+        Action::SelectHome => app.cursor.select_to(0),
+        Action::SelectEnd => app.cursor.select_to(app.buffer_len()),
+        Action::SelectAll => app.cursor.set_selection(0, app.buffer_len()),
+        Action::DeleteBackward => app.text_backspace_event(),
+        Action::DeleteForward => app.text_delete_event(),
+        Action::DeleteForwardOrExit => {
+            if app.buffer.is_empty() {
+                app.set_buffer("exit".to_string());
+                return Some(submit(app));
+            }
+            app.text_delete_event();
+        }
+        Action::KillLineBackward => app.kill_to(0),
+        Action::KillLineForward => app.kill_to(app.buffer_len()),
+        Action::KillWordBackward => {
+            app.cursor.clear_selection();
+            app.text_move_cursor_to_prev_word(true);
+            app.kill_selected_text();
+        }
+        Action::KillWordForward => {
+            app.cursor.clear_selection();
+            app.text_move_cursor_to_next_word(true);
+            app.kill_selected_text();
+        }
+        Action::Yank => app.yank(),
+        Action::YankPop => app.yank_pop(),
+        Action::Copy => {
+            let text = if app.cursor.selection_size() > 0 {
+                app.text_selected()
+            } else {
+                app.buffer.clone()
+            };
+            clipboard::set_text(&text);
+        }
+        Action::Cut => {
+            if app.cursor.selection_size() > 0 {
+                let text = app.text_selected();
+                clipboard::set_text(&text);
+                app.text_backspace_event();
+            } else {
+                let text = app.buffer.clone();
+                clipboard::set_text(&text);
+                app.clear_buffer();
+            }
+        }
+        Action::Paste => app.insert_text(clipboard::get_text()),
+        Action::HistoryPrev => {
+            if let Some(buf) = app.history.prev() {
+                app.set_buffer(buf);
+            }
+        }
+        Action::HistoryNext => {
+            match app.history.next() {
+                Some(buf) => app.set_buffer(buf),
+                None => app.clear_buffer(),
+            }
+        }
+        Action::ScrollUp => app.scroll_up(1),
+        Action::ScrollDown => app.scroll_down(1),
+        Action::PageUp => app.scroll_up(app.text_area.height as usize),
+        Action::PageDown => app.scroll_down(app.text_area.height as usize),
+        Action::ScrollTop => app.scroll_up(usize::MAX),
+        Action::ScrollBottom => app.scroll_pos = 0,
+        Action::Search => app.search = Some(Search::new()),
+        Action::SelectLines => {
+            let rows = app.visible_rows();
+            let last = rows.last().map(|(idx, _, _)| *idx).unwrap_or(0);
+            app.line_selection = Some(LineSelection::new(last));
+        }
+        Action::TogglePreview => app.preview.toggle(),
+        Action::ToggleBrowser => app.browser.toggle(),
+        Action::NormalMode => app.keymap.mode = EditMode::Normal,
+        Action::InsertMode => app.keymap.mode = EditMode::Insert,
+        Action::InsertAfter => {
+            app.text_move_cursor(1, false);
+            app.keymap.mode = EditMode::Insert;
+        }
+        Action::InsertAtStart => {
+            app.cursor.move_to(0);
+            app.keymap.mode = EditMode::Insert;
+        }
+        Action::InsertAtEnd => {
+            app.cursor.move_to(app.buffer_len());
+            app.keymap.mode = EditMode::Insert;
+        }
+    }
+    None
+}
+
+/// Process a key event during the search in the scrollback.
+fn process_search_key(app: &mut App, code: KeyCode, modifiers: KeyModifiers) {
+    let mut search = match app.search.take() {
+        Some(search) => search,
+        None => return,
+    };
+    let nlines = app.lines.len();
+    match (code, modifiers) {
+        (KeyCode::Esc, _) => return,
+        (KeyCode::Enter, _) | (KeyCode::Up, _) | (KeyCode::Char('f'), KeyModifiers::CONTROL) => {
+            // search for older lines
+            let before = search.current.unwrap_or(nlines);
+            if let Some(idx) = search.find_prev(&app.lines, before) {
+                search.current = Some(idx);
+            }
+        },
+        (KeyCode::Down, _) => {
+            // search for newer lines
+            if let Some(idx) = search.current.and_then(|cur| search.find_next(&app.lines, cur)) {
+                search.current = Some(idx);
+            }
+        },
+        (KeyCode::Backspace, _) => {
+            search.query.pop();
+            search.current = search.find_prev(&app.lines, nlines);
+        },
+        (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+            search.query.push(c);
+            search.current = search.find_prev(&app.lines, nlines);
+        },
+        _ => {},
+    }
+    if let Some(idx) = search.current {
+        app.scroll_to_line(idx);
+    }
+    app.search = Some(search);
+}
 
-    // NOTE: height of text area is 2 less than the height of the terminal (two borders).
-    let h = rect.height as usize - 2;
-    if app.lines.len() >= h {
-        let max_scroll_pos = app.lines.len() - h;
-        if app.scroll_pos > max_scroll_pos {
-            app.scroll_pos = max_scroll_pos;
+/// Process a key event during selecting lines in the scrollback.
+fn process_selection_key(app: &mut App, code: KeyCode, modifiers: KeyModifiers) {
+    let mut selection = match app.line_selection.take() {
+        Some(selection) => selection,
+        None => return,
+    };
+    let keep_selection = modifiers.contains(KeyModifiers::SHIFT);
+    let page = app.text_area.height as usize;
+    let last = app.lines.len().saturating_sub(1);
+    match code {
+        KeyCode::Esc => return,
+        KeyCode::Enter | KeyCode::Char('y') | KeyCode::Char('c') => {
+            clipboard::set_text(&selection.text(&app.lines));
+            return;
+        },
+        KeyCode::Up => selection.move_to(selection.pos.saturating_sub(1), keep_selection),
+        KeyCode::Down => selection.move_to((selection.pos + 1).min(last), keep_selection),
+        KeyCode::PageUp => selection.move_to(selection.pos.saturating_sub(page), keep_selection),
+        KeyCode::PageDown => selection.move_to((selection.pos + page).min(last), keep_selection),
+        KeyCode::Home => selection.move_to(0, keep_selection),
+        KeyCode::End => selection.move_to(last, keep_selection),
+        _ => {},
+    }
+    app.scroll_to_line(selection.pos);
+    app.line_selection = Some(selection);
+}
+
+fn process_mouse(app: &mut App, mouse: MouseEvent) {
+    match mouse.kind {
+        MouseEventKind::ScrollUp => {
+            app.scroll_up(_WHEEL_SCROLL);
+        }
+        MouseEventKind::ScrollDown => {
+            app.scroll_down(_WHEEL_SCROLL);
         }
+        MouseEventKind::Down(MouseButton::Left) => {
+            app.click(mouse.column, mouse.row);
+        }
+        _ => {}
     }
+}
 
-    let input = Paragraph::new(app.get_text(h))
-        .style(Style::default().fg(Color::Yellow))
-        .block(Block::default().borders(Borders::ALL)
-        .title("VTree"));
+fn render_ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let rect = f.size();
+    let (rect, preview_rect) = if app.preview.visible && rect.width >= _MIN_PREVIEW_WIDTH {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(rect);
+        (chunks[0], Some(chunks[1]))
+    } else {
+        (rect, None)
+    };
+
+    let block = Block::default().borders(Borders::ALL).title(app.title());
+    app.text_area = block.inner(rect);
+    app.scroll_pos = app.scroll_pos.min(app.max_scroll());
+    let input = Paragraph::new(app.get_text())
+        .style(app.palette.border)
+        .block(block);
     
     f.render_widget(input, rect);
+
+    if let Some(preview_rect) = preview_rect {
+        app.update_preview();
+        let h = preview_rect.height.saturating_sub(2) as usize;
+        let preview = Paragraph::new(app.preview.get_text(h))
+            .style(app.palette.border)
+            .block(Block::default().borders(Borders::ALL)
+            .title(app.preview.title()));
+        f.render_widget(preview, preview_rect);
+    }
 }
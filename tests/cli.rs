@@ -0,0 +1,154 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Create an empty project directory with an initialized .vtree directory.
+fn project(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    std::fs::create_dir_all(&dir).unwrap();
+    assert!(vtree(&dir, &["init"]).status.success());
+    dir
+}
+
+/// Run vtree in `dir`.
+fn vtree(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_vtree"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn test_tree_commands() {
+    let dir = project("tree-commands");
+    std::fs::write(dir.join("a.txt"), "hello\n").unwrap();
+    assert!(vtree(&dir, &["new", "t"]).status.success());
+    assert!(vtree(&dir, &["mkdir", "t", "docs"]).status.success());
+    assert!(vtree(&dir, &["add", "t", "a.txt", "docs/a.txt"]).status.success());
+    assert_eq!(stdout(&vtree(&dir, &["ls", "t", "docs"])), "a.txt\n");
+    assert_eq!(stdout(&vtree(&dir, &["cat", "t", "docs/a.txt"])), "hello\n");
+
+    // user errors are reported without a panic
+    for args in [&["cat", "t", "docs"][..], &["cat", "t", "missing.txt"], &["ls", "unknown"]] {
+        let output = vtree(&dir, args);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("vtree: "));
+    }
+}
+
+#[test]
+fn test_rename_copy_describe() {
+    let dir = project("rename-copy-describe");
+    assert!(vtree(&dir, &["new", "t"]).status.success());
+    assert!(vtree(&dir, &["new", "u"]).status.success());
+
+    // invalid or existing names are refused
+    for args in [
+        &["rename", "t", "u"][..],
+        &["rename", "t", "a/b"],
+        &["rename", "t", ".t"],
+        &["rename", "missing", "v"],
+        &["copy", "t", "u"],
+        &["copy", "t", ""],
+    ] {
+        assert_eq!(vtree(&dir, args).status.code(), Some(1), "{:?}", args);
+    }
+
+    assert!(vtree(&dir, &["rename", "u", "v"]).status.success());
+    assert!(!dir.join(".vtree/trees/u.json").exists());
+    assert_eq!(stdout(&vtree(&dir, &["list", "v"])), "v\n");
+
+    // describe sets the description shown by list, and "" clears it
+    assert!(vtree(&dir, &["describe", "v", "some data"]).status.success());
+    assert_eq!(stdout(&vtree(&dir, &["list", "v"])), "v: some data\n");
+    assert!(vtree(&dir, &["describe", "v", ""]).status.success());
+    assert_eq!(stdout(&vtree(&dir, &["list", "v"])), "v\n");
+}
+
+#[test]
+fn test_copy_files() {
+    let dir = project("copy-files");
+    std::fs::write(dir.join(".vtree/virtual-files/notes.txt"), "notes\n").unwrap();
+    assert!(vtree(&dir, &["new", "t"]).status.success());
+    assert!(vtree(&dir, &["add", "t", ".vtree/virtual-files/notes.txt", "notes.txt"]).status.success());
+
+    // without --files the copy shares the virtual file
+    assert!(vtree(&dir, &["copy", "t", "shared"]).status.success());
+    let original = stdout(&vtree(&dir, &["resolve", "t/notes.txt"]));
+    assert_eq!(stdout(&vtree(&dir, &["resolve", "shared/notes.txt"])), original);
+
+    // with --files the copy has its own file
+    assert!(vtree(&dir, &["copy", "t", "separate", "--files"]).status.success());
+    let copy = stdout(&vtree(&dir, &["resolve", "separate/notes.txt"]));
+    assert_ne!(copy, original);
+    std::fs::write(copy.trim_end(), "changed\n").unwrap();
+    assert_eq!(stdout(&vtree(&dir, &["cat", "t", "notes.txt"])), "notes\n");
+    assert_eq!(stdout(&vtree(&dir, &["cat", "separate", "notes.txt"])), "changed\n");
+}
+
+#[test]
+fn test_remove_and_restore() {
+    let dir = project("remove-and-restore");
+    std::fs::write(dir.join(".vtree/virtual-files/notes.txt"), "notes\n").unwrap();
+    std::fs::write(dir.join(".vtree/virtual-files/own.txt"), "own\n").unwrap();
+    assert!(vtree(&dir, &["new", "t"]).status.success());
+    assert!(vtree(&dir, &["add", "t", ".vtree/virtual-files/notes.txt", "notes.txt"]).status.success());
+    assert!(vtree(&dir, &["add", "t", ".vtree/virtual-files/own.txt", "own.txt"]).status.success());
+    assert!(vtree(&dir, &["copy", "t", "t2"]).status.success());
+    assert!(vtree(&dir, &["rm", "t2", "own.txt"]).status.success());
+
+    // without --yes, the removal must be confirmed
+    let output = vtree(&dir, &["remove", "t"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(dir.join(".vtree/trees/t.json").exists());
+
+    // the file shared with t2 is not moved to the trash
+    assert!(vtree(&dir, &["remove", "t", "--yes"]).status.success());
+    assert!(!dir.join(".vtree/trees/t.json").exists());
+    assert!(!dir.join(".vtree/virtual-files/own.txt").exists());
+    assert_eq!(stdout(&vtree(&dir, &["cat", "t2", "notes.txt"])), "notes\n");
+    assert!(stdout(&vtree(&dir, &["trash", "list"])).starts_with("t (just now, 1 virtual files)"));
+
+    assert!(vtree(&dir, &["restore", "t"]).status.success());
+    assert_eq!(stdout(&vtree(&dir, &["cat", "t", "notes.txt"])), "notes\n");
+    assert_eq!(stdout(&vtree(&dir, &["cat", "t", "own.txt"])), "own\n");
+    assert_eq!(vtree(&dir, &["restore", "t"]).status.code(), Some(1));
+}
+
+#[test]
+fn test_config_errors() {
+    let dir = project("config-errors");
+    for args in [&["config", "get", "nosuch"][..], &["config", "set", "scrollback-size", "0"]] {
+        let output = vtree(&dir, args);
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("vtree: "));
+    }
+    assert!(vtree(&dir, &["config", "set", "scrollback-size", "10"]).status.success());
+    assert_eq!(stdout(&vtree(&dir, &["config", "get", "scrollback-size"])), "10\n");
+}
+
+#[test]
+fn test_copy_and_rm() {
+    let dir = project("copy-and-rm");
+    let file = dir.join(".vtree/virtual-files/x.txt");
+    std::fs::write(&file, "x\n").unwrap();
+    assert!(vtree(&dir, &["new", "t"]).status.success());
+    assert!(vtree(&dir, &["add", "t", file.to_str().unwrap(), "x.txt"]).status.success());
+    assert!(vtree(&dir, &["copy", "t", "t3"]).status.success());
+
+    // the file shared with t is kept
+    assert!(vtree(&dir, &["rm", "t3", "x.txt"]).status.success());
+    assert!(file.exists());
+    assert_eq!(stdout(&vtree(&dir, &["cat", "t", "x.txt"])), "x\n");
+
+    // the file is removed with the last item using it
+    assert!(vtree(&dir, &["rm", "t", "x.txt"]).status.success());
+    assert!(!file.exists());
+}